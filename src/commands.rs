@@ -1,3 +1,4 @@
+use std::cell::Cell;
 use std::collections::HashMap;
 
 use anyhow::{bail, Result};
@@ -6,125 +7,440 @@ use swayipc::Connection;
 use tokio::sync::mpsc;
 use tracing::{debug, info, warn};
 
-use crate::config::TomlConfig;
+use crate::config::{ButtonAction, Source, SwipeSpeed, SwitchCondition, TomlConfig, Trigger};
+use crate::keyboard::Modifiers;
+use crate::pointer::button_code;
+use crate::switch::Switches;
+
+const SEQUENCE_TIMEOUT_MS: u64 = 600;
 
 #[derive(Debug)]
 pub struct CommandDesc {
     dry_run: bool,
-    tx: mpsc::Sender<InputCommand>,
-    mappings: HashMap<InputCommand, OutputCommand>,
+    tx: mpsc::Sender<(InputCommand, CommandContext)>,
+    mappings: HashMap<InputCommand, Vec<OutputCommand>>,
+    modifiers: Cell<Modifiers>,
+    switches: Cell<Switches>,
+    last_key_usec: Cell<Option<u64>>,
+    typing_timeout_ms: Option<u64>,
 }
 
 impl CommandDesc {
-    pub fn new(dry_run: bool, config: TomlConfig, tx: mpsc::Sender<InputCommand>) -> Self {
-        let mut mappings = HashMap::new();
-
-        config
-            .mappings
-            .iter()
-            .for_each(|x| match (x.gesture.as_str(), x.finger_count) {
-                ("swipe_left", Some(n)) => {
-                    mappings.insert(
-                        InputCommand::SwipeLeft(n),
-                        OutputCommand {
-                            cmd: x.cmd.clone(),
-                            cmd_type: x.cmd_type.clone(),
-                        },
-                    );
-                }
-                ("swipe_right", Some(n)) => {
-                    mappings.insert(
-                        InputCommand::SwipeRight(n),
-                        OutputCommand {
-                            cmd: x.cmd.clone(),
-                            cmd_type: x.cmd_type.clone(),
-                        },
-                    );
-                }
-                ("swipe_up", Some(n)) => {
-                    mappings.insert(
-                        InputCommand::SwipeUp(n),
-                        OutputCommand {
-                            cmd: x.cmd.clone(),
-                            cmd_type: x.cmd_type.clone(),
-                        },
-                    );
-                }
-                ("swipe_down", Some(n)) => {
-                    mappings.insert(
-                        InputCommand::SwipeDown(n),
-                        OutputCommand {
-                            cmd: x.cmd.clone(),
-                            cmd_type: x.cmd_type.clone(),
-                        },
-                    );
-                }
-                ("scrollwheel_left", None) => {
-                    mappings.insert(
-                        InputCommand::ScrollLeft,
-                        OutputCommand {
-                            cmd: x.cmd.clone(),
-                            cmd_type: x.cmd_type.clone(),
-                        },
-                    );
-                }
-                ("scrollwheel_right", None) => {
-                    mappings.insert(
-                        InputCommand::ScrollRight,
-                        OutputCommand {
-                            cmd: x.cmd.clone(),
-                            cmd_type: x.cmd_type.clone(),
-                        },
-                    );
-                }
-                _ => warn!("Unsupported mapping: {:?}", x),
+    pub fn new(
+        dry_run: bool,
+        config: &TomlConfig,
+        tx: mpsc::Sender<(InputCommand, CommandContext)>,
+    ) -> Self {
+        let mut mappings: HashMap<InputCommand, Vec<OutputCommand>> = HashMap::new();
+
+        for x in &config.mappings {
+            let cmd = match (x.gesture.as_str(), &x.sequence) {
+                ("sequence", Some(steps)) => steps
+                    .iter()
+                    .map(|step| InputCommand::from_gesture(&step.gesture, step.finger_count))
+                    .collect::<Option<Vec<_>>>()
+                    .filter(|steps| steps.len() > 1)
+                    .map(InputCommand::Sequence),
+                ("shape", None) => x
+                    .shape
+                    .clone()
+                    .zip(x.finger_count)
+                    .map(|(name, n)| InputCommand::Shape(name, n)),
+                ("mouse_shape", None) => x
+                    .shape
+                    .clone()
+                    .map(|name| InputCommand::Mouse(Box::new(InputCommand::Shape(name, 0)))),
+                ("pad_button", None) => x
+                    .number
+                    .map(|n| InputCommand::PadButton(n, x.action.unwrap_or_default())),
+                ("pad_ring_cw", None) => Some(InputCommand::PadRingCw(x.number.unwrap_or(0))),
+                ("pad_ring_ccw", None) => Some(InputCommand::PadRingCcw(x.number.unwrap_or(0))),
+                ("pad_strip_up", None) => Some(InputCommand::PadStripUp(x.number.unwrap_or(0))),
+                ("pad_strip_down", None) => Some(InputCommand::PadStripDown(x.number.unwrap_or(0))),
+                ("button", None) => x
+                    .button
+                    .as_deref()
+                    .and_then(button_code)
+                    .map(|code| InputCommand::Button(code, x.action.unwrap_or_default())),
+                (gesture, None) => InputCommand::from_gesture(gesture, x.finger_count),
+                _ => None,
+            };
+
+            let Some(cmd) = cmd else {
+                warn!("Unsupported mapping: {:?}", x);
+                continue;
+            };
+
+            mappings.entry(cmd).or_default().push(OutputCommand {
+                cmd: x.cmd.clone(),
+                cmd_type: x.cmd_type.clone(),
+                min_duration_ms: x.min_duration_ms,
+                repeat_every: x.repeat_every,
+                interval_ms: x.interval_ms,
+                speed: x.speed,
+                trigger: x.trigger.unwrap_or_default(),
+                timeout_ms: x.timeout_ms,
+                source: x.source,
+                modifiers: x.modifiers.as_deref().map(Modifiers::new),
+                switch: x.switch,
+                mode: x.mode,
             });
+        }
 
         Self {
             dry_run,
             tx,
             mappings,
+            modifiers: Cell::new(Modifiers::default()),
+            switches: Cell::new(Switches::default()),
+            last_key_usec: Cell::new(None),
+            typing_timeout_ms: config.recognition.typing_timeout_ms,
         }
     }
 
     pub async fn send(&self, cmd: InputCommand) -> Result<()> {
-        self.tx.send(cmd).await?;
+        self.send_with_context(cmd, CommandContext::default()).await
+    }
+
+    /// Keyboard modifiers held from now on, attached to the commands sent
+    pub fn set_modifiers(&self, modifiers: Modifiers) {
+        self.modifiers.set(modifiers);
+    }
+
+    /// Lid and tablet mode state from now on, attached to the commands sent
+    pub fn set_switches(&self, switches: Switches) {
+        self.switches.set(switches);
+    }
+
+    /// Time of the last key press other than a modifier
+    pub fn set_last_key(&self, usec: u64) {
+        self.last_key_usec.set(Some(usec));
+    }
+
+    /// Whether something starting at `usec` comes too soon after a key press
+    pub fn typing(&self, usec: u64) -> bool {
+        let (Some(last), Some(timeout)) = (self.last_key_usec.get(), self.typing_timeout_ms) else {
+            return false;
+        };
+
+        last <= usec && usec - last < timeout * 1000
+    }
+
+    pub async fn send_with_context(
+        &self,
+        cmd: InputCommand,
+        mut ctx: CommandContext,
+    ) -> Result<()> {
+        ctx.modifiers = self.modifiers.get();
+        ctx.switches = self.switches.get();
+        self.tx.send((cmd, ctx)).await?;
         Ok(())
     }
+
+    /// Queue a command without waiting, returns false when the channel is full
+    pub fn try_send_with_context(
+        &self,
+        cmd: InputCommand,
+        mut ctx: CommandContext,
+    ) -> Result<bool> {
+        ctx.modifiers = self.modifiers.get();
+        ctx.switches = self.switches.get();
+        match self.tx.try_send((cmd, ctx)) {
+            Ok(()) => Ok(true),
+            Err(mpsc::error::TrySendError::Full(_)) => Ok(false),
+            Err(err) => bail!("Failed to queue command: {}", err),
+        }
+    }
+
+    /// Minimum time between two commands of an analog mapping, None when
+    /// `cmd` is not mapped
+    pub fn analog_interval(&self, cmd: &InputCommand) -> Option<u64> {
+        let mappings = self.mappings.get(cmd)?;
+        Some(mappings.iter().find_map(|x| x.interval_ms).unwrap_or(0))
+    }
+
+    /// Distance after which a swipe mapped to `cmd` fires again while the
+    /// fingers are still moving
+    pub fn repeat_every(&self, cmd: &InputCommand) -> Option<f64> {
+        self.mappings
+            .get(cmd)?
+            .iter()
+            .find_map(|x| x.repeat_every)
+            .filter(|step| *step > 0.0)
+    }
+
+    /// Threshold when any mapping of `cmd` fires as soon as it is recognized
+    pub fn trigger(&self, cmd: &InputCommand) -> Trigger {
+        let threshold = self
+            .mappings
+            .get(cmd)
+            .is_some_and(|x| x.iter().any(|x| x.trigger == Trigger::Threshold));
+
+        if threshold {
+            Trigger::Threshold
+        } else {
+            Trigger::End
+        }
+    }
+
+    /// Whether any shape is mapped for this finger count
+    pub fn has_shapes(&self, finger_count: i32) -> bool {
+        self.mappings
+            .keys()
+            .any(|cmd| matches!(cmd, InputCommand::Shape(_, n) if *n == finger_count))
+    }
+
+    /// Whether any pointer stroke is mapped
+    pub fn has_mouse(&self) -> bool {
+        self.mappings.keys().any(|cmd| match cmd {
+            InputCommand::Mouse(_) => true,
+            InputCommand::Sequence(steps) => {
+                steps.iter().any(|x| matches!(x, InputCommand::Mouse(_)))
+            }
+            _ => false,
+        })
+    }
+
+    /// Whether any mapping may fire on a touchscreen gesture
+    pub fn has_touchscreen(&self) -> bool {
+        self.mappings.iter().any(|(cmd, x)| {
+            cmd.touchscreen()
+                && x.iter()
+                    .any(|x| x.source.is_none_or(|source| source == Source::Touchscreen))
+        })
+    }
+
+    pub fn contains(&self, cmd: &InputCommand) -> bool {
+        self.mappings.contains_key(cmd)
+    }
+
+    /// Whether `cmd` is mapped on its own or as a step of a sequence
+    pub fn mapped(&self, cmd: &InputCommand) -> bool {
+        self.mappings.keys().any(|x| match x {
+            InputCommand::Sequence(steps) => steps.contains(cmd),
+            x => x == cmd,
+        })
+    }
+
+    /// Time window of the sequences starting with `cmds` and longer than
+    /// them, None when there is no such sequence
+    pub fn sequence_timeout(&self, cmds: &[InputCommand]) -> Option<u64> {
+        self.mappings
+            .iter()
+            .filter(|(cmd, _)| match cmd {
+                InputCommand::Sequence(steps) => {
+                    steps.len() > cmds.len() && steps.starts_with(cmds)
+                }
+                _ => false,
+            })
+            .flat_map(|(_, x)| x.iter())
+            .map(|x| x.timeout_ms.unwrap_or(SEQUENCE_TIMEOUT_MS))
+            .max()
+    }
+
+    fn lookup(&self, cmd: &InputCommand, ctx: &CommandContext) -> Option<&OutputCommand> {
+        self.mappings
+            .get(cmd)?
+            .iter()
+            .filter(|x| x.matches(ctx))
+            .max_by_key(|x| x.specificity())
+    }
 }
 
-#[derive(Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum InputCommand {
     SwipeUp(i32),
     SwipeDown(i32),
     SwipeLeft(i32),
     SwipeRight(i32),
+    SwipeUpLeft(i32),
+    SwipeUpRight(i32),
+    SwipeDownLeft(i32),
+    SwipeDownRight(i32),
+    PinchIn(i32),
+    PinchOut(i32),
+    RotateCw(i32),
+    RotateCcw(i32),
+    Hold(i32),
+    Swipe(i32),
+    Pinch(i32),
     ScrollLeft,
     ScrollRight,
+    ScrollUp,
+    ScrollDown,
+    ScrollFingerLeft,
+    ScrollFingerRight,
+    ScrollFingerUp,
+    ScrollFingerDown,
+    EdgeLeft,
+    EdgeRight,
+    EdgeTop,
+    EdgeBottom,
+    LidOpen,
+    LidClose,
+    TabletModeOn,
+    TabletModeOff,
+    Sequence(Vec<InputCommand>),
+    Shape(String, i32),
+    Button(u32, ButtonAction),
+    /// Tablet pad button, ring or strip by number
+    PadButton(u32, ButtonAction),
+    PadRingCw(u32),
+    PadRingCcw(u32),
+    PadStripUp(u32),
+    PadStripDown(u32),
+    /// Swipe or shape drawn with the pointer, without a finger count
+    Mouse(Box<InputCommand>),
+}
+
+/// Properties of a recognized gesture, used to select one of the mappings
+/// bound to the same input command and to fill in analog command templates
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CommandContext {
+    pub duration_ms: u64,
+    pub dx: f64,
+    pub dy: f64,
+    pub scale: f64,
+    pub angle: f64,
+    pub speed: Option<SwipeSpeed>,
+    pub source: Option<Source>,
+    pub modifiers: Modifiers,
+    pub switches: Switches,
+    pub pad_mode: Option<u32>,
 }
 
 #[derive(Debug)]
 struct OutputCommand {
     cmd: String,
     cmd_type: String,
+    min_duration_ms: Option<u64>,
+    repeat_every: Option<f64>,
+    interval_ms: Option<u64>,
+    speed: Option<SwipeSpeed>,
+    trigger: Trigger,
+    timeout_ms: Option<u64>,
+    source: Option<Source>,
+    modifiers: Option<Modifiers>,
+    switch: Option<SwitchCondition>,
+    mode: Option<u32>,
+}
+
+impl OutputCommand {
+    fn command(&self, ctx: &CommandContext) -> String {
+        self.cmd
+            .replace("{dx}", &format!("{:.0}", ctx.dx))
+            .replace("{dy}", &format!("{:.0}", ctx.dy))
+            .replace("{scale}", &format!("{:.2}", ctx.scale))
+            .replace("{angle}", &format!("{:.0}", ctx.angle))
+    }
+
+    fn matches(&self, ctx: &CommandContext) -> bool {
+        self.min_duration_ms
+            .is_none_or(|min| ctx.duration_ms >= min)
+            && self.speed.is_none_or(|speed| ctx.speed == Some(speed))
+            && self.source.is_none_or(|source| ctx.source == Some(source))
+            && self.modifiers.is_none_or(|x| ctx.modifiers == x)
+            && self.switch.is_none_or(|x| ctx.switches.satisfies(x))
+            && self.mode.is_none_or(|x| ctx.pad_mode == Some(x))
+    }
+
+    /// Mappings with more qualifiers win over the generic ones
+    fn specificity(&self) -> (bool, bool, bool, bool, bool, Option<u64>) {
+        (
+            self.mode.is_some(),
+            self.switch.is_some(),
+            self.modifiers.is_some(),
+            self.source.is_some(),
+            self.speed.is_some(),
+            self.min_duration_ms,
+        )
+    }
 }
 
 impl InputCommand {
-    pub fn process_command(self, cmd_desc: &CommandDesc) -> Result<()> {
-        let cmd = &cmd_desc.mappings.get(&self);
+    /// Whether the touchscreen recognizes this command
+    fn touchscreen(&self) -> bool {
+        match self {
+            InputCommand::SwipeUp(_)
+            | InputCommand::SwipeDown(_)
+            | InputCommand::SwipeLeft(_)
+            | InputCommand::SwipeRight(_)
+            | InputCommand::SwipeUpLeft(_)
+            | InputCommand::SwipeUpRight(_)
+            | InputCommand::SwipeDownLeft(_)
+            | InputCommand::SwipeDownRight(_)
+            | InputCommand::PinchIn(_)
+            | InputCommand::PinchOut(_)
+            | InputCommand::RotateCw(_)
+            | InputCommand::RotateCcw(_)
+            | InputCommand::Hold(_)
+            | InputCommand::EdgeLeft
+            | InputCommand::EdgeRight
+            | InputCommand::EdgeTop
+            | InputCommand::EdgeBottom => true,
+            InputCommand::Sequence(steps) => steps.iter().any(InputCommand::touchscreen),
+            _ => false,
+        }
+    }
+
+    fn from_gesture(gesture: &str, finger_count: Option<i32>) -> Option<Self> {
+        let cmd = match (gesture, finger_count) {
+            ("swipe_left", Some(n)) => InputCommand::SwipeLeft(n),
+            ("swipe_right", Some(n)) => InputCommand::SwipeRight(n),
+            ("swipe_up", Some(n)) => InputCommand::SwipeUp(n),
+            ("swipe_down", Some(n)) => InputCommand::SwipeDown(n),
+            ("swipe_up_left", Some(n)) => InputCommand::SwipeUpLeft(n),
+            ("swipe_up_right", Some(n)) => InputCommand::SwipeUpRight(n),
+            ("swipe_down_left", Some(n)) => InputCommand::SwipeDownLeft(n),
+            ("swipe_down_right", Some(n)) => InputCommand::SwipeDownRight(n),
+            ("pinch_in", Some(n)) => InputCommand::PinchIn(n),
+            ("pinch_out", Some(n)) => InputCommand::PinchOut(n),
+            ("rotate_cw", Some(n)) => InputCommand::RotateCw(n),
+            ("rotate_ccw", Some(n)) => InputCommand::RotateCcw(n),
+            ("hold", Some(n)) => InputCommand::Hold(n),
+            ("swipe", Some(n)) => InputCommand::Swipe(n),
+            ("pinch", Some(n)) => InputCommand::Pinch(n),
+            ("scrollwheel_left", None) => InputCommand::ScrollLeft,
+            ("scrollwheel_right", None) => InputCommand::ScrollRight,
+            ("scrollwheel_up", None) => InputCommand::ScrollUp,
+            ("scrollwheel_down", None) => InputCommand::ScrollDown,
+            ("scrollfinger_left", None) => InputCommand::ScrollFingerLeft,
+            ("scrollfinger_right", None) => InputCommand::ScrollFingerRight,
+            ("scrollfinger_up", None) => InputCommand::ScrollFingerUp,
+            ("scrollfinger_down", None) => InputCommand::ScrollFingerDown,
+            ("edge_left", None) => InputCommand::EdgeLeft,
+            ("edge_right", None) => InputCommand::EdgeRight,
+            ("edge_top", None) => InputCommand::EdgeTop,
+            ("edge_bottom", None) => InputCommand::EdgeBottom,
+            ("lid_open", None) => InputCommand::LidOpen,
+            ("lid_close", None) => InputCommand::LidClose,
+            ("tablet_mode_on", None) => InputCommand::TabletModeOn,
+            ("tablet_mode_off", None) => InputCommand::TabletModeOff,
+            (gesture, None) if gesture.starts_with("mouse_") => {
+                let swipe = gesture.replacen("mouse_", "swipe_", 1);
+                InputCommand::Mouse(Box::new(InputCommand::from_gesture(&swipe, Some(0))?))
+            }
+            _ => return None,
+        };
+
+        Some(cmd)
+    }
+
+    pub fn process_command(self, ctx: &CommandContext, cmd_desc: &CommandDesc) -> Result<()> {
+        let cmd = cmd_desc.lookup(&self, ctx);
 
         let Some(cmd) = cmd else {
             bail!("Command not in configuration: {:?}", self);
         };
 
         if cmd_desc.dry_run {
-            info!(?cmd, "Dry run, command: ");
+            info!(?cmd, command = cmd.command(ctx), "Dry run, command: ");
             return Ok(());
         }
 
         match cmd.cmd_type.as_str() {
-            "sway" => sway::process_command(&cmd.cmd)?,
-            "builtin" => builtin::process_command(&cmd.cmd)?,
+            "sway" => sway::process_command(&cmd.command(ctx))?,
+            "builtin" => builtin::process_command(&cmd.command(ctx))?,
             cmd_type => warn!(?cmd_type, "Command type not supported"),
         }
 
@@ -189,32 +505,33 @@ mod builtin {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::config::Mapping;
+    use crate::config::{Mapping, Modifier, SwitchCondition};
 
     #[test]
     fn test_command_desc_new() {
         let (tx, _) = mpsc::channel(1);
-        let config = TomlConfig { mappings: vec![] };
+        let config = TomlConfig::default();
 
-        let cmd_desc = CommandDesc::new(false, config, tx);
+        let cmd_desc = CommandDesc::new(false, &config, tx);
 
-        assert_eq!(cmd_desc.dry_run, false);
+        assert!(!cmd_desc.dry_run);
         assert_eq!(cmd_desc.mappings.len(), 0);
     }
 
     #[tokio::test]
     async fn test_command_desc_recv() {
         let (tx, mut rx) = mpsc::channel(1);
-        let config = TomlConfig { mappings: vec![] };
+        let config = TomlConfig::default();
 
-        let cmd_desc = CommandDesc::new(true, config, tx);
+        let cmd_desc = CommandDesc::new(true, &config, tx);
 
         let cmd = InputCommand::SwipeUp(3);
         let res = cmd_desc.send(cmd).await;
-        assert_eq!(res.is_ok(), true);
+        assert!(res.is_ok());
 
-        let cmd = rx.recv().await.unwrap();
+        let (cmd, ctx) = rx.recv().await.unwrap();
         assert_eq!(cmd, InputCommand::SwipeUp(3));
+        assert_eq!(ctx, CommandContext::default());
     }
 
     #[tokio::test]
@@ -226,13 +543,303 @@ mod test {
                 finger_count: Some(3),
                 cmd: "workspace_new".to_string(),
                 cmd_type: "sway".to_string(),
+                ..Default::default()
             }],
+            ..Default::default()
         };
 
-        let cmd_desc = CommandDesc::new(false, config, tx);
+        let cmd_desc = CommandDesc::new(false, &config, tx);
 
         let cmd = InputCommand::SwipeDown(3);
-        let res = cmd.process_command(&cmd_desc);
-        assert_eq!(res.is_err(), true);
+        let res = cmd.process_command(&CommandContext::default(), &cmd_desc);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_command_desc_hold_duration() {
+        let (tx, _) = mpsc::channel(1);
+        let config = TomlConfig {
+            mappings: vec![
+                Mapping {
+                    gesture: "hold".to_string(),
+                    finger_count: Some(3),
+                    cmd: "short".to_string(),
+                    cmd_type: "sway".to_string(),
+                    ..Default::default()
+                },
+                Mapping {
+                    gesture: "hold".to_string(),
+                    finger_count: Some(3),
+                    cmd: "long".to_string(),
+                    cmd_type: "sway".to_string(),
+                    min_duration_ms: Some(800),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let ctx = CommandContext {
+            duration_ms: 300,
+            ..Default::default()
+        };
+        let cmd = cmd_desc.lookup(&InputCommand::Hold(3), &ctx).unwrap();
+        assert_eq!(cmd.cmd, "short");
+
+        let ctx = CommandContext {
+            duration_ms: 1200,
+            ..Default::default()
+        };
+        let cmd = cmd_desc.lookup(&InputCommand::Hold(3), &ctx).unwrap();
+        assert_eq!(cmd.cmd, "long");
+
+        let ctx = CommandContext {
+            duration_ms: 1200,
+            ..Default::default()
+        };
+        assert!(cmd_desc.lookup(&InputCommand::Hold(4), &ctx).is_none());
+    }
+
+    #[test]
+    fn test_output_command_template() {
+        let cmd = OutputCommand {
+            cmd: "resize grow width {dx}px height {dy}px; exec vol {scale} {angle}".to_string(),
+            cmd_type: "sway".to_string(),
+            min_duration_ms: None,
+            repeat_every: None,
+            interval_ms: Some(50),
+            speed: None,
+            trigger: Trigger::End,
+            timeout_ms: None,
+            source: None,
+            modifiers: None,
+            switch: None,
+            mode: None,
+        };
+
+        let ctx = CommandContext {
+            dx: 12.4,
+            dy: -3.6,
+            scale: 1.256,
+            angle: 15.0,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            cmd.command(&ctx),
+            "resize grow width 12px height -4px; exec vol 1.26 15"
+        );
+    }
+
+    #[test]
+    fn test_command_desc_swipe_speed() {
+        let (tx, _) = mpsc::channel(1);
+        let config = TomlConfig {
+            mappings: vec![
+                Mapping {
+                    gesture: "swipe_left".to_string(),
+                    finger_count: Some(3),
+                    cmd: "workspace prev".to_string(),
+                    cmd_type: "sway".to_string(),
+                    ..Default::default()
+                },
+                Mapping {
+                    gesture: "swipe_left".to_string(),
+                    finger_count: Some(3),
+                    cmd: "move container to workspace prev".to_string(),
+                    cmd_type: "sway".to_string(),
+                    speed: Some(SwipeSpeed::Fast),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let ctx = CommandContext {
+            speed: Some(SwipeSpeed::Fast),
+            ..Default::default()
+        };
+        let cmd = cmd_desc.lookup(&InputCommand::SwipeLeft(3), &ctx).unwrap();
+        assert_eq!(cmd.cmd, "move container to workspace prev");
+
+        let ctx = CommandContext {
+            speed: Some(SwipeSpeed::Slow),
+            ..Default::default()
+        };
+        let cmd = cmd_desc.lookup(&InputCommand::SwipeLeft(3), &ctx).unwrap();
+        assert_eq!(cmd.cmd, "workspace prev");
+
+        let cmd = cmd_desc
+            .lookup(&InputCommand::SwipeLeft(3), &CommandContext::default())
+            .unwrap();
+        assert_eq!(cmd.cmd, "workspace prev");
+    }
+
+    #[test]
+    fn test_command_desc_source() {
+        let (tx, _) = mpsc::channel(1);
+        let config = TomlConfig {
+            mappings: vec![
+                Mapping {
+                    gesture: "swipe_left".to_string(),
+                    finger_count: Some(3),
+                    cmd: "workspace prev".to_string(),
+                    cmd_type: "sway".to_string(),
+                    ..Default::default()
+                },
+                Mapping {
+                    gesture: "swipe_left".to_string(),
+                    finger_count: Some(3),
+                    cmd: "focus left".to_string(),
+                    cmd_type: "sway".to_string(),
+                    source: Some(Source::Touchscreen),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let ctx = CommandContext {
+            source: Some(Source::Touchscreen),
+            ..Default::default()
+        };
+        let cmd = cmd_desc.lookup(&InputCommand::SwipeLeft(3), &ctx).unwrap();
+        assert_eq!(cmd.cmd, "focus left");
+
+        let ctx = CommandContext {
+            source: Some(Source::Touchpad),
+            ..Default::default()
+        };
+        let cmd = cmd_desc.lookup(&InputCommand::SwipeLeft(3), &ctx).unwrap();
+        assert_eq!(cmd.cmd, "workspace prev");
+    }
+
+    #[test]
+    fn test_command_desc_modifiers() {
+        let (tx, _) = mpsc::channel(1);
+        let config = TomlConfig {
+            mappings: vec![
+                Mapping {
+                    gesture: "swipe_left".to_string(),
+                    finger_count: Some(3),
+                    cmd: "workspace prev".to_string(),
+                    cmd_type: "sway".to_string(),
+                    ..Default::default()
+                },
+                Mapping {
+                    gesture: "swipe_left".to_string(),
+                    finger_count: Some(3),
+                    cmd: "move container to workspace prev".to_string(),
+                    cmd_type: "sway".to_string(),
+                    modifiers: Some(vec![Modifier::Super]),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let ctx = CommandContext {
+            modifiers: Modifiers::new(&[Modifier::Super]),
+            ..Default::default()
+        };
+        let cmd = cmd_desc.lookup(&InputCommand::SwipeLeft(3), &ctx).unwrap();
+        assert_eq!(cmd.cmd, "move container to workspace prev");
+
+        let ctx = CommandContext {
+            modifiers: Modifiers::new(&[Modifier::Super, Modifier::Shift]),
+            ..Default::default()
+        };
+        let cmd = cmd_desc.lookup(&InputCommand::SwipeLeft(3), &ctx).unwrap();
+        assert_eq!(cmd.cmd, "workspace prev");
+    }
+
+    #[test]
+    fn test_command_desc_switch() {
+        let (tx, _) = mpsc::channel(1);
+        let config = TomlConfig {
+            mappings: vec![
+                Mapping {
+                    gesture: "swipe_left".to_string(),
+                    finger_count: Some(3),
+                    cmd: "workspace prev".to_string(),
+                    cmd_type: "sway".to_string(),
+                    ..Default::default()
+                },
+                Mapping {
+                    gesture: "swipe_left".to_string(),
+                    finger_count: Some(3),
+                    cmd: "focus left".to_string(),
+                    cmd_type: "sway".to_string(),
+                    switch: Some(SwitchCondition::TabletModeOn),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let cmd = cmd_desc
+            .lookup(&InputCommand::SwipeLeft(3), &CommandContext::default())
+            .unwrap();
+        assert_eq!(cmd.cmd, "workspace prev");
+
+        let ctx = CommandContext {
+            switches: Switches {
+                tablet_mode: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let cmd = cmd_desc.lookup(&InputCommand::SwipeLeft(3), &ctx).unwrap();
+        assert_eq!(cmd.cmd, "focus left");
+    }
+
+    #[test]
+    fn test_command_desc_pad_mode() {
+        let (tx, _) = mpsc::channel(1);
+        let config = TomlConfig {
+            mappings: vec![
+                Mapping {
+                    gesture: "pad_ring_cw".to_string(),
+                    cmd: "workspace next".to_string(),
+                    cmd_type: "sway".to_string(),
+                    ..Default::default()
+                },
+                Mapping {
+                    gesture: "pad_ring_cw".to_string(),
+                    cmd: "focus right".to_string(),
+                    cmd_type: "sway".to_string(),
+                    mode: Some(1),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let ctx = CommandContext {
+            pad_mode: Some(1),
+            ..Default::default()
+        };
+        let cmd = cmd_desc.lookup(&InputCommand::PadRingCw(0), &ctx).unwrap();
+        assert_eq!(cmd.cmd, "focus right");
+
+        let ctx = CommandContext {
+            pad_mode: Some(0),
+            ..Default::default()
+        };
+        let cmd = cmd_desc.lookup(&InputCommand::PadRingCw(0), &ctx).unwrap();
+        assert_eq!(cmd.cmd, "workspace next");
+        assert!(cmd_desc.lookup(&InputCommand::PadRingCw(1), &ctx).is_none());
     }
 }
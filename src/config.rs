@@ -1,19 +1,443 @@
-use std::{env, fs, path::PathBuf};
+use std::{env, f64::consts::PI, fs, path::PathBuf};
 
 use anyhow::{bail, Context, Result};
 use serde_derive::Deserialize;
+use tracing::warn;
 
-#[derive(Debug, Deserialize)]
+/// libinput normalizes the unaccelerated motion of touchpads to a 1000dpi
+/// device whatever their resolution, so one millimeter on the touchpad is
+/// this many units. Accelerated motion has no such relation to distance
+pub const UNITS_PER_MM: f64 = 1000.0 / 25.4;
+
+/// Largest part of the shorter side of a touchpad a threshold in millimeters
+/// may span
+const MAX_THRESHOLD_FRACTION: f64 = 0.8;
+
+#[derive(Debug, Default, Deserialize)]
 pub struct TomlConfig {
     pub mappings: Vec<Mapping>,
+    #[serde(default)]
+    pub pinch: PinchConfig,
+    #[serde(default)]
+    pub swipe: SwipeConfig,
+    #[serde(default)]
+    pub recognition: RecognitionConfig,
+    #[serde(default)]
+    pub shapes: ShapesConfig,
+    #[serde(default)]
+    pub touchscreen: TouchscreenConfig,
+    #[serde(default)]
+    pub scroll: ScrollConfig,
+    #[serde(default)]
+    pub mouse: MouseConfig,
+    #[serde(default)]
+    pub pad: PadConfig,
+    #[serde(default)]
+    pub devices: DevicesConfig,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct Mapping {
     pub gesture: String,
     pub cmd: String,
     pub cmd_type: String,
     pub finger_count: Option<i32>,
+    pub min_duration_ms: Option<u64>,
+    pub repeat_every: Option<f64>,
+    pub interval_ms: Option<u64>,
+    pub speed: Option<SwipeSpeed>,
+    pub trigger: Option<Trigger>,
+    pub sequence: Option<Vec<SequenceStep>>,
+    pub timeout_ms: Option<u64>,
+    pub shape: Option<String>,
+    pub source: Option<Source>,
+    pub button: Option<String>,
+    pub action: Option<ButtonAction>,
+    pub modifiers: Option<Vec<Modifier>>,
+    pub switch: Option<SwitchCondition>,
+    /// Button, ring or strip number on a tablet pad, starting at 0
+    pub number: Option<u32>,
+    /// Tablet pad mode the button, ring or strip has to be in
+    pub mode: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SequenceStep {
+    pub gesture: String,
+    pub finger_count: Option<i32>,
+}
+
+/// When a swipe or pinch mapping fires
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Trigger {
+    /// Once the fingers are lifted
+    #[default]
+    End,
+    /// As soon as the gesture is recognized
+    Threshold,
+}
+
+/// Mouse button transition a button mapping fires on, long presses are told
+/// apart on release with `min_duration_ms`
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ButtonAction {
+    #[default]
+    Press,
+    Release,
+}
+
+/// Keyboard modifier a mapping requires to be held
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Modifier {
+    Shift,
+    Ctrl,
+    Alt,
+    Super,
+}
+
+/// Switch state a mapping requires, named after the event entering it
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SwitchCondition {
+    LidOpen,
+    LidClose,
+    TabletModeOn,
+    TabletModeOff,
+}
+
+/// Kind of device a gesture was performed on
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Touchpad,
+    Touchscreen,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SwipeSpeed {
+    Fast,
+    Slow,
+}
+
+/// Scale and angle thresholds used to recognize pinch and rotation gestures
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct PinchConfig {
+    /// Fingers moved together: scale at or below this value is a pinch in
+    pub in_threshold: f64,
+    /// Fingers moved apart: scale at or above this value is a pinch out
+    pub out_threshold: f64,
+    /// Rotation in degrees, positive clockwise, past which a rotation is recognized
+    pub rotate_threshold: f64,
+}
+
+impl Default for PinchConfig {
+    fn default() -> Self {
+        PinchConfig {
+            in_threshold: 0.8,
+            out_threshold: 1.25,
+            rotate_threshold: 45.0,
+        }
+    }
+}
+
+/// Angular sectors and speed cutoffs used to classify swipe gestures
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SwipeConfig {
+    /// Width in degrees of each diagonal sector, 0 only recognizes the four
+    /// axis directions
+    pub diagonal_sector: f64,
+    /// Average speed, in units per second, at or above which a swipe is fast
+    pub fast_speed: f64,
+    /// Average speed, in units per second, at or below which a swipe is slow
+    pub slow_speed: f64,
+}
+
+impl Default for SwipeConfig {
+    fn default() -> Self {
+        SwipeConfig {
+            diagonal_sector: 0.0,
+            fast_speed: 2000.0,
+            slow_speed: 600.0,
+        }
+    }
+}
+
+/// Distances, in millimeters on the screen, used to recognize touchscreen
+/// gestures, pinches share the touchpad thresholds
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct TouchscreenConfig {
+    /// Distance along an axis the fingers have to travel for a swipe
+    pub threshold_mm: f64,
+    /// Fingers moving less than this are holding still
+    pub hold_tolerance_mm: f64,
+    /// Time the fingers have to stay still for a hold
+    pub hold_ms: u64,
+    /// Width of the screen borders single finger edge swipes start from
+    pub edge_mm: f64,
+    /// Output the touchscreen is mapped to, defaults to the built-in panel
+    pub output: Option<String>,
+}
+
+impl Default for TouchscreenConfig {
+    fn default() -> Self {
+        TouchscreenConfig {
+            threshold_mm: 20.0,
+            hold_tolerance_mm: 5.0,
+            hold_ms: 500,
+            edge_mm: 5.0,
+            output: None,
+        }
+    }
+}
+
+/// How much scrolling fires a scroll mapping
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ScrollConfig {
+    /// Wheel notches per command, fractions suit high resolution wheels
+    pub detents: f64,
+    /// Scrolling right after a command is ignored for this long
+    pub cooldown_ms: u64,
+    /// Distance a two finger scroll has to cover along its main axis
+    pub finger_threshold: f64,
+}
+
+impl Default for ScrollConfig {
+    fn default() -> Self {
+        ScrollConfig {
+            detents: 1.0,
+            cooldown_ms: 0,
+            finger_threshold: 150.0,
+        }
+    }
+}
+
+/// Strokes drawn with the pointer while holding a button
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct MouseConfig {
+    /// Button held while drawing, by name or event code
+    pub button: String,
+    /// Distance along an axis a stroke has to cover to be recognized
+    pub threshold: f64,
+}
+
+impl Default for MouseConfig {
+    fn default() -> Self {
+        MouseConfig {
+            button: "right".to_string(),
+            threshold: 100.0,
+        }
+    }
+}
+
+/// Rings and strips of tablet pads, a command is sent each time they move by
+/// a step
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct PadConfig {
+    /// Rotation of a ring in degrees
+    pub ring_step: f64,
+    /// Travel along a strip, which spans from 0 to 1
+    pub strip_step: f64,
+}
+
+impl Default for PadConfig {
+    fn default() -> Self {
+        PadConfig {
+            ring_step: 15.0,
+            strip_step: 0.1,
+        }
+    }
+}
+
+/// Devices whose events are processed, all of them by default
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct DevicesConfig {
+    /// Only the devices matching one of these rules, when there are any
+    pub include: Vec<DeviceRule>,
+    /// Devices ignored even when included
+    pub exclude: Vec<DeviceRule>,
+}
+
+/// Criteria a device has to match all of
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct DeviceRule {
+    /// Device name, `*` matches any characters and `?` a single one
+    pub name: Option<String>,
+    pub vendor: Option<u32>,
+    pub product: Option<u32>,
+}
+
+/// Templates of the shapes drawn with a swipe
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ShapesConfig {
+    /// Match score, between 0 and 1, a drawing needs to reach to be recognized
+    pub min_score: f64,
+    pub templates: Vec<ShapeTemplate>,
+}
+
+impl Default for ShapesConfig {
+    fn default() -> Self {
+        ShapesConfig {
+            min_score: 0.8,
+            templates: vec![],
+        }
+    }
+}
+
+/// Points of a shape, as printed by `swayped train <name>`
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ShapeTemplate {
+    pub name: String,
+    pub points: Vec<(f64, f64)>,
+}
+
+/// Swipe recognition thresholds, with overrides per finger count and device
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RecognitionConfig {
+    /// Distance along an axis a swipe has to cover to be recognized
+    pub threshold: f64,
+    /// Same as `threshold` in millimeters, takes precedence when set.
+    /// Requires `unaccelerated`
+    pub threshold_mm: Option<f64>,
+    /// How much the main axis has to dominate the other one, for swipes
+    /// exceeding the threshold along both axes
+    pub axis_ratio: f64,
+    /// Accumulate the motion of the fingers without pointer acceleration
+    pub unaccelerated: bool,
+    /// Swipes and pinches ending sooner are ignored
+    pub min_duration_ms: u64,
+    /// Swipes and pinches lasting longer are ignored
+    pub max_duration_ms: Option<u64>,
+    pub finger_change: FingerChange,
+    /// Cancel swipes ending within this fraction of the farthest distance
+    /// reached from where they started
+    pub reverse_cancel: Option<f64>,
+    /// Swipes, pinches, holds and scrolls starting this soon after a key
+    /// press are ignored
+    pub typing_timeout_ms: Option<u64>,
+    pub overrides: Vec<RecognitionOverride>,
+}
+
+/// What to do with a gesture whose finger count changes before it ends
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FingerChange {
+    #[default]
+    Reject,
+    /// Classify the gesture with the last finger count
+    Reclassify,
+}
+
+/// Thresholds replacing the defaults for the gestures matching all the given
+/// criteria, device overrides win over finger count ones
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct RecognitionOverride {
+    pub finger_count: Option<i32>,
+    pub device: Option<String>,
+    pub threshold: Option<f64>,
+    pub threshold_mm: Option<f64>,
+    pub axis_ratio: Option<f64>,
+}
+
+/// Thresholds in effect for a single gesture
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Thresholds {
+    pub threshold: f64,
+    pub axis_ratio: f64,
+}
+
+impl Default for RecognitionConfig {
+    fn default() -> Self {
+        RecognitionConfig {
+            threshold: 100.0,
+            threshold_mm: None,
+            axis_ratio: (PI / 8.0).tan(),
+            unaccelerated: false,
+            min_duration_ms: 0,
+            max_duration_ms: None,
+            finger_change: FingerChange::Reject,
+            reverse_cancel: None,
+            typing_timeout_ms: None,
+            overrides: vec![],
+        }
+    }
+}
+
+impl RecognitionConfig {
+    pub fn validate(&self) -> Result<()> {
+        let threshold_mm =
+            self.threshold_mm.is_some() || self.overrides.iter().any(|x| x.threshold_mm.is_some());
+        if threshold_mm && !self.unaccelerated {
+            bail!("threshold_mm requires unaccelerated = true, accelerated motion is not in millimeters");
+        }
+        Ok(())
+    }
+
+    /// Thresholds of a gesture on `device`, whose physical size in millimeters
+    /// bounds the thresholds given in millimeters
+    pub fn thresholds(
+        &self,
+        finger_count: i32,
+        device: &str,
+        size: Option<(f64, f64)>,
+    ) -> Thresholds {
+        let mut thresholds = Thresholds {
+            threshold: self.threshold,
+            axis_ratio: self.axis_ratio,
+        };
+        let mut threshold_mm = self.threshold_mm;
+
+        let mut overrides: Vec<&RecognitionOverride> = self
+            .overrides
+            .iter()
+            .filter(|x| x.finger_count.is_none_or(|n| n == finger_count))
+            .filter(|x| x.device.as_ref().is_none_or(|name| name == device))
+            .collect();
+        overrides.sort_by_key(|x| (x.device.is_some(), x.finger_count.is_some()));
+
+        for x in overrides {
+            if x.threshold_mm.is_some() {
+                threshold_mm = x.threshold_mm;
+            } else if let Some(threshold) = x.threshold {
+                thresholds.threshold = threshold;
+                threshold_mm = None;
+            }
+            thresholds.axis_ratio = x.axis_ratio.unwrap_or(thresholds.axis_ratio);
+        }
+
+        if let Some(mut mm) = threshold_mm {
+            // the threshold applies to both axes, a swipe along the short one
+            // must still be able to reach it from wherever the fingers land
+            if let Some((width, height)) = size {
+                let max = width.min(height) * MAX_THRESHOLD_FRACTION;
+                if mm > max {
+                    warn!(
+                        ?mm,
+                        ?width,
+                        ?height,
+                        ?device,
+                        "Threshold too large for the touchpad"
+                    );
+                    mm = max;
+                }
+            }
+            thresholds.threshold = mm * UNITS_PER_MM;
+        }
+
+        thresholds
+    }
 }
 
 impl TomlConfig {
@@ -27,6 +451,7 @@ impl TomlConfig {
             .context(format!("Failed to read configuration file '{}'", file))?;
         let decoded: TomlConfig =
             toml::from_str(&content).context("Failed to decode toml configuration")?;
+        decoded.recognition.validate()?;
         Ok(decoded)
     }
 
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+
+use input::event::{DeviceEvent, EventTrait};
+use input::{Device, DeviceCapability};
+use tracing::{debug, info};
+
+use crate::config::{DeviceRule, DevicesConfig, TomlConfig};
+
+const CAPABILITIES: [DeviceCapability; 7] = [
+    DeviceCapability::Keyboard,
+    DeviceCapability::Pointer,
+    DeviceCapability::Touch,
+    DeviceCapability::TabletTool,
+    DeviceCapability::TabletPad,
+    DeviceCapability::Gesture,
+    DeviceCapability::Switch,
+];
+
+/// Device as reported by libinput when it was added
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub vendor: u32,
+    pub product: u32,
+    pub capabilities: Vec<DeviceCapability>,
+}
+
+impl DeviceInfo {
+    fn new(device: &Device) -> Self {
+        DeviceInfo {
+            name: device.name().to_string(),
+            vendor: device.id_vendor(),
+            product: device.id_product(),
+            capabilities: CAPABILITIES
+                .into_iter()
+                .filter(|x| device.has_capability(*x))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct DeviceEntry {
+    info: DeviceInfo,
+    allowed: bool,
+}
+
+/// Devices of the seat by sysname, and whether the `[devices]` rules let
+/// their events through
+pub struct SwaypedDevices {
+    devices: HashMap<String, DeviceEntry>,
+    config: DevicesConfig,
+}
+
+impl SwaypedDevices {
+    pub fn new(config: &TomlConfig) -> Self {
+        SwaypedDevices {
+            devices: HashMap::new(),
+            config: config.devices.clone(),
+        }
+    }
+
+    /// Returns the device removed when its events were processed, for the
+    /// recognizers to forget about it
+    pub fn handle_event(&mut self, event: &DeviceEvent) -> Option<DeviceInfo> {
+        let device = event.device();
+        match event {
+            DeviceEvent::Added(_) => {
+                self.add(device.sysname(), DeviceInfo::new(&device));
+                None
+            }
+            DeviceEvent::Removed(_) => self.remove(device.sysname()),
+            _ => None,
+        }
+    }
+
+    /// Whether the events of `device` are processed
+    pub fn allowed(&self, device: &Device) -> bool {
+        self.allowed_sysname(device.sysname(), || DeviceInfo::new(device))
+    }
+
+    /// Devices added before swayped started watching are matched on the fly
+    fn allowed_sysname(&self, sysname: &str, info: impl FnOnce() -> DeviceInfo) -> bool {
+        match self.devices.get(sysname) {
+            Some(entry) => entry.allowed,
+            None => self.allows(&info()),
+        }
+    }
+
+    fn add(&mut self, sysname: &str, info: DeviceInfo) {
+        let allowed = self.allows(&info);
+        info!(
+            ?sysname,
+            name = info.name,
+            id = format!("{:04x}:{:04x}", info.vendor, info.product),
+            capabilities = ?info.capabilities,
+            ?allowed,
+            "Device added"
+        );
+        self.devices
+            .insert(sysname.to_string(), DeviceEntry { info, allowed });
+    }
+
+    fn remove(&mut self, sysname: &str) -> Option<DeviceInfo> {
+        let entry = self.devices.remove(sysname)?;
+        info!(?sysname, name = entry.info.name, "Device removed");
+        entry.allowed.then_some(entry.info)
+    }
+
+    fn allows(&self, info: &DeviceInfo) -> bool {
+        let included = self.config.include.is_empty()
+            || self.config.include.iter().any(|x| rule_matches(x, info));
+        let excluded = self.config.exclude.iter().any(|x| rule_matches(x, info));
+        debug!(name = info.name, ?included, ?excluded, "device rules");
+
+        included && !excluded
+    }
+}
+
+fn rule_matches(rule: &DeviceRule, info: &DeviceInfo) -> bool {
+    rule.name.as_ref().is_none_or(|x| glob(x, &info.name))
+        && rule.vendor.is_none_or(|x| x == info.vendor)
+        && rule.product.is_none_or(|x| x == info.product)
+}
+
+/// Matches `name` against `pattern`, where `*` stands for any characters and
+/// `?` for a single one
+fn glob(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut i, mut j) = (0, 0);
+    // position after the last star, and where it started to match in name
+    let mut star = None;
+    while j < name.len() {
+        match pattern.get(i) {
+            Some('*') => {
+                i += 1;
+                star = Some((i, j));
+            }
+            Some(&c) if c == '?' || c == name[j] => {
+                i += 1;
+                j += 1;
+            }
+            _ => {
+                let Some((after, start)) = star else {
+                    return false;
+                };
+                i = after;
+                j = start + 1;
+                star = Some((after, start + 1));
+            }
+        }
+    }
+
+    pattern[i..].iter().all(|x| *x == '*')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_device_glob() {
+        assert!(glob("*Wacom*", "Wacom Intuos S Pad"));
+        assert!(glob("Wacom*Pad", "Wacom Intuos S Pad"));
+        assert!(glob("SYNA????:00*", "SYNA8004:00 06CB:CD8B Touchpad"));
+        assert!(glob("*", ""));
+        assert!(!glob("Wacom*Pen", "Wacom Intuos S Pad"));
+        assert!(!glob("Touchpad", "SYNA8004:00 06CB:CD8B Touchpad"));
+    }
+
+    #[test]
+    fn test_swayped_devices_rules() {
+        let info = |name: &str, vendor, product| DeviceInfo {
+            name: name.to_string(),
+            vendor,
+            product,
+            capabilities: vec![DeviceCapability::Pointer, DeviceCapability::Gesture],
+        };
+
+        let mut config = TomlConfig::default();
+        let devices = SwaypedDevices::new(&config);
+        assert!(devices.allows(&info("Apple Inc. Magic Trackpad", 0x05ac, 0x0265)));
+
+        config.devices = DevicesConfig {
+            include: vec![DeviceRule {
+                name: Some("*Touchpad".to_string()),
+                ..Default::default()
+            }],
+            exclude: vec![DeviceRule {
+                vendor: Some(0x056a),
+                ..Default::default()
+            }],
+        };
+        let mut devices = SwaypedDevices::new(&config);
+
+        devices.add(
+            "event5",
+            info("SYNA8004:00 06CB:CD8B Touchpad", 0x06cb, 0xcd8b),
+        );
+        devices.add("event12", info("Apple Inc. Magic Trackpad", 0x05ac, 0x0265));
+        devices.add("event14", info("Wacom Intuos Touchpad", 0x056a, 0x0374));
+
+        let unknown = || info("Wacom Intuos Touchpad", 0x056a, 0x0374);
+        assert!(devices.allowed_sysname("event5", unknown));
+        assert!(!devices.allowed_sysname("event12", unknown));
+        assert!(!devices.allowed_sysname("event14", unknown));
+
+        // not registered yet, the rules decide
+        assert!(!devices.allowed_sysname("event20", unknown));
+        assert!(devices.allowed_sysname("event21", || {
+            info("ELAN0001:00 04F3:3140 Touchpad", 0x04f3, 0x3140)
+        }));
+
+        // only the devices whose events were processed have state to forget
+        assert_eq!(devices.remove("event12"), None);
+        assert_eq!(devices.remove("event5").map(|x| x.product), Some(0xcd8b));
+        assert_eq!(devices.remove("event5"), None);
+        assert_eq!(devices.devices.len(), 1);
+    }
+}
@@ -1,37 +1,96 @@
 use anyhow::Result;
 use input::event::gesture::GestureSwipeEvent::{Begin, End, Update};
 use input::event::gesture::{
-    GestureEventCoordinates, GestureEventTrait, GestureSwipeBeginEvent, GestureSwipeEndEvent,
-    GestureSwipeEvent, GestureSwipeUpdateEvent,
+    GestureEndEvent, GestureEventCoordinates, GestureEventTrait, GestureHoldBeginEvent,
+    GestureHoldEndEvent, GestureHoldEvent, GesturePinchBeginEvent, GesturePinchEndEvent,
+    GesturePinchEvent, GesturePinchEventTrait, GesturePinchUpdateEvent, GestureSwipeBeginEvent,
+    GestureSwipeEndEvent, GestureSwipeEvent, GestureSwipeUpdateEvent,
 };
-use std::f64::consts::PI;
+use input::event::EventTrait;
+use input::{ffi, AsRaw};
 use tracing::{debug, trace};
 
-use crate::commands::{CommandDesc, InputCommand};
-
-const SWIPE_DIST_THRESHOLD: f64 = 100.0;
+use crate::commands::{CommandContext, CommandDesc, InputCommand};
+use crate::config::{
+    FingerChange, PinchConfig, RecognitionConfig, Source, SwipeConfig, SwipeSpeed, Thresholds,
+    TomlConfig, Trigger,
+};
+use crate::shape::{Point, ShapeRecognizer};
 
 pub struct SwaypedGesture<'a> {
     dx: f64,
     dy: f64,
+    path: Vec<Point>,
+    peak: f64,
+    scale: f64,
+    angle: f64,
+    begin_usec: u64,
+    time_usec: u64,
+    repeats: u32,
+    analog: SwaypedAnalog,
+    rejected: bool,
+    triggered: bool,
     finger_count: i32,
+    pinch: PinchConfig,
+    swipe: SwipeConfig,
+    recognition: RecognitionConfig,
+    thresholds: Thresholds,
+    shapes: ShapeRecognizer,
     cmd_desc: &'a CommandDesc,
 }
 
+/// Motion not yet reported to an analog mapping
+#[derive(Debug, Default)]
+struct SwaypedAnalog {
+    active: bool,
+    dx: f64,
+    dy: f64,
+    angle: f64,
+    scale: f64,
+    usec: u64,
+}
+
 #[derive(Debug)]
 enum SwaypedSwipeDir {
     Up,
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+#[derive(Debug)]
+enum SwaypedPinchDir {
+    In,
+    Out,
+    Cw,
+    Ccw,
 }
 
 impl<'a> SwaypedGesture<'a> {
-    pub fn new(cmd_desc: &'a CommandDesc) -> Self {
+    pub fn new(cmd_desc: &'a CommandDesc, config: &TomlConfig) -> Self {
         SwaypedGesture {
             dx: 0.0,
             dy: 0.0,
+            path: vec![(0.0, 0.0)],
+            peak: 0.0,
+            scale: 1.0,
+            angle: 0.0,
+            begin_usec: 0,
+            time_usec: 0,
+            repeats: 0,
+            analog: SwaypedAnalog::default(),
+            rejected: false,
+            triggered: false,
             finger_count: 0,
+            pinch: config.pinch.clone(),
+            swipe: config.swipe.clone(),
+            recognition: config.recognition.clone(),
+            thresholds: config.recognition.thresholds(0, "", None),
+            shapes: ShapeRecognizer::new(&config.shapes),
             cmd_desc,
         }
     }
@@ -39,6 +98,16 @@ impl<'a> SwaypedGesture<'a> {
     pub fn reset(&mut self) -> Result<()> {
         self.dx = 0.0;
         self.dy = 0.0;
+        self.path = vec![(0.0, 0.0)];
+        self.peak = 0.0;
+        self.scale = 1.0;
+        self.angle = 0.0;
+        self.begin_usec = 0;
+        self.time_usec = 0;
+        self.repeats = 0;
+        self.analog = SwaypedAnalog::default();
+        self.rejected = false;
+        self.triggered = false;
         self.finger_count = 0;
         Ok(())
     }
@@ -46,71 +115,587 @@ impl<'a> SwaypedGesture<'a> {
     fn begin(&mut self, event: &GestureSwipeBeginEvent) -> Result<()> {
         trace!(finger_count = ?event.finger_count(), "begin gesture");
         self.reset()?;
+        self.begin_usec = event.time_usec();
+        self.finger_count = event.finger_count();
+        self.typing_filter();
+        let device = event.device();
+        self.thresholds =
+            self.recognition
+                .thresholds(event.finger_count(), device.name(), device.size());
+        trace!(?self.thresholds, "begin gesture");
         Ok(())
     }
 
-    fn update(&mut self, event: &GestureSwipeUpdateEvent) -> Result<()> {
-        trace!(dx = ?event.dx(), dy = ?event.dy(), finger_count = ?event.finger_count(), "update gesture");
-        self.dx += event.dx();
-        self.dy += event.dy();
-        self.finger_count = event.finger_count();
+    async fn update(&mut self, event: &GestureSwipeUpdateEvent) -> Result<()> {
+        let (dx, dy) = if self.recognition.unaccelerated {
+            (event.dx_unaccelerated(), event.dy_unaccelerated())
+        } else {
+            (event.dx(), event.dy())
+        };
+        trace!(?dx, ?dy, finger_count = ?event.finger_count(), "update gesture");
+        self.process_motion(dx, dy, event.finger_count(), event.time_usec())
+            .await
+    }
+
+    async fn process_motion(
+        &mut self,
+        dx: f64,
+        dy: f64,
+        finger_count: i32,
+        usec: u64,
+    ) -> Result<()> {
+        self.dx += dx;
+        self.dy += dy;
+        self.path.push((self.dx, self.dy));
+        self.peak = self.peak.max(self.dx.hypot(self.dy));
+        self.time_usec = usec;
+        self.update_filters(finger_count);
+        if self.rejected || self.triggered {
+            return Ok(());
+        }
+
+        self.analog.dx += dx;
+        self.analog.dy += dy;
+        self.process_analog_swipe(usec)?;
+        // an analog mapping takes over the whole gesture, as on termination
+        if self.analog.active {
+            return Ok(());
+        }
+
+        let ctx = CommandContext {
+            speed: self.swipe_speed(),
+            ..self.timed_context()
+        };
+        self.process_threshold(self.swipe_command(), ctx.clone())
+            .await?;
+        self.process_repeat(ctx)?;
         Ok(())
     }
 
-    async fn terminate(&self, event: &GestureSwipeEndEvent) -> Result<()> {
-        trace!(finger_count = ?event.finger_count(), "terminate gesture");
-        trace!(?self.dx, ?self.dy, ?self.finger_count, "terminate gesture");
-        self.process_swipe().await?;
+    async fn terminate(&mut self, event: &GestureSwipeEndEvent) -> Result<()> {
+        trace!(finger_count = ?event.finger_count(), cancelled = ?event.cancelled(), "terminate gesture");
+        self.time_usec = event.time_usec();
+        trace!(?self.dx, ?self.dy, ?self.finger_count, ?self.repeats, "terminate gesture");
+        if !event.cancelled()
+            && self.accepted()
+            && !self.reversed()
+            && self.repeats == 0
+            && !self.analog.active
+            && !self.triggered
+        {
+            self.process_swipe().await?;
+        }
         Ok(())
     }
 
     pub async fn handle_event(&mut self, event: &GestureSwipeEvent) -> Result<()> {
         match event {
             Begin(event) => self.begin(event)?,
-            Update(event) => self.update(event)?,
+            Update(event) => self.update(event).await?,
             End(event) => self.terminate(event).await?,
             &_ => (),
         }
         Ok(())
     }
 
+    fn pinch_begin(&mut self, event: &GesturePinchBeginEvent) -> Result<()> {
+        trace!(finger_count = ?event.finger_count(), "begin pinch");
+        self.reset()?;
+        self.begin_usec = event.time_usec();
+        self.finger_count = event.finger_count();
+        self.typing_filter();
+        Ok(())
+    }
+
+    async fn pinch_update(&mut self, event: &GesturePinchUpdateEvent) -> Result<()> {
+        trace!(scale = ?event.scale(), angle_delta = ?event.angle_delta(), finger_count = ?event.finger_count(), "update pinch");
+        self.scale = event.scale();
+        self.angle += event.angle_delta();
+        self.time_usec = event.time_usec();
+        self.update_filters(event.finger_count());
+        if self.rejected || self.triggered {
+            return Ok(());
+        }
+
+        self.analog.angle += event.angle_delta();
+        self.process_analog_pinch(event.time_usec())?;
+        if self.analog.active {
+            return Ok(());
+        }
+
+        self.process_threshold(self.pinch_command(), self.timed_context())
+            .await?;
+        Ok(())
+    }
+
+    async fn pinch_terminate(&mut self, event: &GesturePinchEndEvent) -> Result<()> {
+        trace!(finger_count = ?event.finger_count(), cancelled = ?event.cancelled(), "terminate pinch");
+        self.time_usec = event.time_usec();
+        trace!(?self.scale, ?self.angle, ?self.finger_count, "terminate pinch");
+        if !event.cancelled() && self.accepted() && !self.analog.active && !self.triggered {
+            self.process_pinch().await?;
+        }
+        Ok(())
+    }
+
+    pub async fn handle_pinch_event(&mut self, event: &GesturePinchEvent) -> Result<()> {
+        match event {
+            GesturePinchEvent::Begin(event) => self.pinch_begin(event)?,
+            GesturePinchEvent::Update(event) => self.pinch_update(event).await?,
+            GesturePinchEvent::End(event) => self.pinch_terminate(event).await?,
+            &_ => (),
+        }
+        Ok(())
+    }
+
+    fn hold_begin(&mut self, event: &GestureHoldBeginEvent) -> Result<()> {
+        trace!(finger_count = ?event.finger_count(), "begin hold");
+        self.reset()?;
+        self.begin_usec = event.time_usec();
+        self.finger_count = event.finger_count();
+        self.typing_filter();
+        Ok(())
+    }
+
+    async fn hold_terminate(&self, event: &GestureHoldEndEvent) -> Result<()> {
+        // GestureHoldEndEvent does not implement GestureEndEvent, although
+        // libinput reports holds cancelled by finger motion the same way
+        let cancelled =
+            unsafe { ffi::libinput_event_gesture_get_cancelled(event.as_raw_mut()) != 0 };
+        let duration_ms = event.time_usec().saturating_sub(self.begin_usec) / 1000;
+        trace!(?cancelled, ?duration_ms, ?self.finger_count, "terminate hold");
+
+        if cancelled || self.rejected {
+            return Ok(());
+        }
+
+        self.process_hold(duration_ms).await?;
+        Ok(())
+    }
+
+    pub async fn handle_hold_event(&mut self, event: &GestureHoldEvent) -> Result<()> {
+        match event {
+            GestureHoldEvent::Begin(event) => self.hold_begin(event)?,
+            GestureHoldEvent::End(event) => self.hold_terminate(event).await?,
+            &_ => (),
+        }
+        Ok(())
+    }
+
+    /// Reject gestures started while typing, most likely a thumb or a palm
+    /// brushing the touchpad
+    fn typing_filter(&mut self) {
+        if self.cmd_desc.typing(self.begin_usec) {
+            debug!("typing, rejecting gesture");
+            self.rejected = true;
+        }
+    }
+
+    /// Reject gestures whose finger count changed or lasting too long, unless
+    /// configured to follow the finger count
+    fn update_filters(&mut self, finger_count: i32) {
+        if finger_count != self.finger_count {
+            match self.recognition.finger_change {
+                FingerChange::Reject => {
+                    debug!(from = ?self.finger_count, to = ?finger_count, "finger count changed, rejecting gesture");
+                    self.rejected = true;
+                }
+                FingerChange::Reclassify => {
+                    debug!(from = ?self.finger_count, to = ?finger_count, "finger count changed");
+                    self.finger_count = finger_count;
+                }
+            }
+        }
+
+        let duration_ms = self.time_usec.saturating_sub(self.begin_usec) / 1000;
+        if self
+            .recognition
+            .max_duration_ms
+            .is_some_and(|max| duration_ms > max)
+        {
+            debug!(?duration_ms, "gesture too long, rejecting");
+            self.rejected = true;
+        }
+    }
+
+    fn accepted(&self) -> bool {
+        let duration_ms = self.time_usec.saturating_sub(self.begin_usec) / 1000;
+        let accepted = !self.rejected
+            && duration_ms >= self.recognition.min_duration_ms
+            && self
+                .recognition
+                .max_duration_ms
+                .is_none_or(|max| duration_ms <= max);
+
+        if !accepted {
+            debug!(?duration_ms, ?self.rejected, "gesture filtered out");
+        }
+
+        accepted
+    }
+
+    /// Check whether the fingers came back close enough to where the swipe
+    /// started for the user to have changed their mind
+    fn reversed(&self) -> bool {
+        let Some(fraction) = self.recognition.reverse_cancel else {
+            return false;
+        };
+
+        let distance = self.dx.hypot(self.dy);
+        let reversed = distance <= self.peak * fraction;
+        if reversed {
+            debug!(?distance, ?self.peak, "swipe reversed, cancelling");
+        }
+
+        reversed
+    }
+
+    fn context(&self) -> CommandContext {
+        CommandContext {
+            source: Some(Source::Touchpad),
+            ..Default::default()
+        }
+    }
+
+    /// Context of a gesture lasting until the last event received
+    fn timed_context(&self) -> CommandContext {
+        CommandContext {
+            duration_ms: self.time_usec.saturating_sub(self.begin_usec) / 1000,
+            ..self.context()
+        }
+    }
+
+    fn swipe_command(&self) -> Option<InputCommand> {
+        swipe_command(
+            self.dx,
+            self.dy,
+            self.finger_count,
+            &self.swipe,
+            &self.thresholds,
+        )
+    }
+
+    fn swipe_speed(&self) -> Option<SwipeSpeed> {
+        let duration = self.time_usec.saturating_sub(self.begin_usec) as f64 / 1_000_000.0;
+        if duration <= 0.0 {
+            return None;
+        }
+
+        let velocity = self.dx.hypot(self.dy) / duration;
+        trace!(?velocity, "swipe_speed");
+
+        if velocity >= self.swipe.fast_speed {
+            Some(SwipeSpeed::Fast)
+        } else if velocity <= self.swipe.slow_speed {
+            Some(SwipeSpeed::Slow)
+        } else {
+            None
+        }
+    }
+
+    /// Shape drawn by the swipe, when it matches one of the mapped templates
+    fn shape_command(&self) -> Option<InputCommand> {
+        if !self.cmd_desc.has_shapes(self.finger_count) {
+            return None;
+        }
+
+        let (name, score) = self.shapes.recognize(&self.path)?;
+        debug!(?name, ?score, "shape recognized");
+        let cmd = InputCommand::Shape(name.to_string(), self.finger_count);
+        self.cmd_desc.contains(&cmd).then_some(cmd)
+    }
+
     async fn process_swipe(&self) -> Result<()> {
-        use SwaypedSwipeDir::*;
+        let finger_count = self.finger_count;
+        let swipe = self.swipe_command();
+        let speed = self.swipe_speed();
+        let shape = self.shape_command();
+
+        debug!(?swipe, ?shape, ?speed, ?finger_count, "swipe_process");
+
+        let Some(cmd) = shape.or(swipe) else {
+            return Ok(());
+        };
+
+        let ctx = CommandContext {
+            speed,
+            ..self.timed_context()
+        };
+        self.cmd_desc.send_with_context(cmd, ctx).await?;
 
-        let dx = self.dx;
-        let dy = self.dy;
+        Ok(())
+    }
+
+    fn process_repeat(&mut self, ctx: CommandContext) -> Result<()> {
+        let Some(cmd) = self.swipe_command() else {
+            return Ok(());
+        };
+
+        let Some(step) = self.cmd_desc.repeat_every(&cmd) else {
+            return Ok(());
+        };
+
+        let distance = match cmd {
+            InputCommand::SwipeLeft(_) | InputCommand::SwipeRight(_) => self.dx.abs(),
+            InputCommand::SwipeUp(_) | InputCommand::SwipeDown(_) => self.dy.abs(),
+            _ => self.dx.hypot(self.dy),
+        };
+
+        // commands are consumed by the same task, which would block forever
+        // on a full channel, fire at most one step per update and leave it for
+        // the next update when the channel is full
+        if f64::from(self.repeats + 1) * step > distance {
+            return Ok(());
+        }
+
+        if self.cmd_desc.try_send_with_context(cmd.clone(), ctx)? {
+            self.repeats += 1;
+            debug!(?cmd, ?self.repeats, "swipe_repeat");
+        }
+
+        Ok(())
+    }
+
+    /// Check whether an analog mapping of `cmd` is due for a new command,
+    /// marking the gesture as analog so that it fires no discrete command
+    fn analog_due(&mut self, cmd: &InputCommand, usec: u64) -> bool {
+        let Some(interval) = self.cmd_desc.analog_interval(cmd) else {
+            return false;
+        };
+
+        self.analog.active = true;
+        self.analog.usec == 0 || usec.saturating_sub(self.analog.usec) >= interval * 1000
+    }
+
+    fn process_analog_swipe(&mut self, usec: u64) -> Result<()> {
+        let cmd = InputCommand::Swipe(self.finger_count);
+        if !self.analog_due(&cmd, usec) {
+            return Ok(());
+        }
+
+        // only report whole units, the remainder is kept for the next command
+        let dx = self.analog.dx.round();
+        let dy = self.analog.dy.round();
+        if dx == 0.0 && dy == 0.0 {
+            return Ok(());
+        }
+
+        let ctx = CommandContext {
+            dx,
+            dy,
+            ..self.context()
+        };
+
+        // analog commands may come faster than they are processed, keep the
+        // motion for later instead of blocking when the channel is full
+        if self.cmd_desc.try_send_with_context(cmd, ctx)? {
+            trace!(?dx, ?dy, "swipe_analog");
+            self.analog.dx -= dx;
+            self.analog.dy -= dy;
+            self.analog.usec = usec;
+        }
+
+        Ok(())
+    }
+
+    fn process_analog_pinch(&mut self, usec: u64) -> Result<()> {
+        let cmd = InputCommand::Pinch(self.finger_count);
+        if !self.analog_due(&cmd, usec) {
+            return Ok(());
+        }
+
+        let scale = self.scale;
+        let angle = self.analog.angle.round();
+        if (scale - self.analog.scale).abs() < 0.01 && angle == 0.0 {
+            return Ok(());
+        }
+
+        let ctx = CommandContext {
+            scale,
+            angle,
+            ..self.context()
+        };
+
+        if self.cmd_desc.try_send_with_context(cmd, ctx)? {
+            trace!(?scale, ?angle, "pinch_analog");
+            self.analog.angle -= angle;
+            self.analog.scale = scale;
+            self.analog.usec = usec;
+        }
+
+        Ok(())
+    }
+
+    fn pinch_command(&self) -> Option<InputCommand> {
+        pinch_command(self.scale, self.angle, self.finger_count, &self.pinch)
+    }
+
+    async fn process_pinch(&self) -> Result<()> {
+        let pinch = self.pinch_command();
+
+        debug!(?pinch, ?self.scale, ?self.angle, ?self.finger_count, "pinch_process");
+
+        let Some(cmd) = pinch else {
+            return Ok(());
+        };
+
+        self.cmd_desc.send_with_context(cmd, self.context()).await?;
+
+        Ok(())
+    }
+
+    /// Fire as soon as the gesture is recognized when its mapping asks for it,
+    /// the rest of the gesture is then ignored
+    async fn process_threshold(
+        &mut self,
+        cmd: Option<InputCommand>,
+        ctx: CommandContext,
+    ) -> Result<()> {
+        let Some(cmd) = cmd else {
+            return Ok(());
+        };
+
+        if self.cmd_desc.trigger(&cmd) != Trigger::Threshold || !self.accepted() {
+            return Ok(());
+        }
+
+        debug!(?cmd, ?ctx, ?self.finger_count, "threshold_process");
+        self.triggered = true;
+        self.cmd_desc.send_with_context(cmd, ctx).await?;
+
+        Ok(())
+    }
+
+    async fn process_hold(&self, duration_ms: u64) -> Result<()> {
         let finger_count = self.finger_count;
 
-        let mut ratio: f64 = PI / 8.0;
-        ratio = ratio.tan();
+        debug!(?duration_ms, ?finger_count, "hold_process");
+
+        // libinput reports a hold on every tap and pointer stop
+        let cmd = InputCommand::Hold(finger_count);
+        if !self.cmd_desc.mapped(&cmd) {
+            return Ok(());
+        }
+
+        let ctx = CommandContext {
+            duration_ms,
+            ..self.context()
+        };
+        self.cmd_desc.send_with_context(cmd, ctx).await?;
+
+        Ok(())
+    }
+}
 
-        let mut swipe: Option<SwaypedSwipeDir> = None;
-        if dx.abs() >= SWIPE_DIST_THRESHOLD && dy.abs() >= SWIPE_DIST_THRESHOLD {
-            if (dx.abs() / dy.abs()) > (dy.abs() / dx.abs() + ratio) {
-                swipe = if dx > 0.0 { Some(Right) } else { Some(Left) }
-            } else if (dy.abs() / dx.abs()) > (dx.abs() / dy.abs() + ratio) {
-                swipe = if dy > 0.0 { Some(Down) } else { Some(Up) }
+fn swipe_direction(
+    dx: f64,
+    dy: f64,
+    swipe: &SwipeConfig,
+    thresholds: &Thresholds,
+) -> Option<SwaypedSwipeDir> {
+    use SwaypedSwipeDir::*;
+
+    let threshold = thresholds.threshold;
+    if dx.abs() <= threshold && dy.abs() <= threshold {
+        return None;
+    }
+
+    let sector = swipe.diagonal_sector;
+    if sector > 0.0 {
+        let angle = dy.abs().atan2(dx.abs()).to_degrees();
+        return if (angle - 45.0).abs() < sector / 2.0 {
+            match (dx > 0.0, dy > 0.0) {
+                (true, true) => Some(DownRight),
+                (true, false) => Some(UpRight),
+                (false, true) => Some(DownLeft),
+                (false, false) => Some(UpLeft),
+            }
+        } else if angle < 45.0 {
+            if dx > 0.0 {
+                Some(Right)
+            } else {
+                Some(Left)
             }
-        } else if dx.abs() > SWIPE_DIST_THRESHOLD {
+        } else if dy > 0.0 {
+            Some(Down)
+        } else {
+            Some(Up)
+        };
+    }
+
+    let ratio = thresholds.axis_ratio;
+
+    let mut swipe: Option<SwaypedSwipeDir> = None;
+    if dx.abs() >= threshold && dy.abs() >= threshold {
+        if (dx.abs() / dy.abs()) > (dy.abs() / dx.abs() + ratio) {
             swipe = if dx > 0.0 { Some(Right) } else { Some(Left) }
-        } else if dy.abs() > SWIPE_DIST_THRESHOLD {
+        } else if (dy.abs() / dx.abs()) > (dx.abs() / dy.abs() + ratio) {
             swipe = if dy > 0.0 { Some(Down) } else { Some(Up) }
         }
+    } else if dx.abs() > threshold {
+        swipe = if dx > 0.0 { Some(Right) } else { Some(Left) }
+    } else if dy.abs() > threshold {
+        swipe = if dy > 0.0 { Some(Down) } else { Some(Up) }
+    }
 
-        debug!(?swipe, ?finger_count, "swipe_process");
+    swipe
+}
 
-        let cmd = match swipe {
-            Some(Up) => InputCommand::SwipeUp(finger_count),
-            Some(Down) => InputCommand::SwipeDown(finger_count),
-            Some(Left) => InputCommand::SwipeLeft(finger_count),
-            Some(Right) => InputCommand::SwipeRight(finger_count),
-            None => return Ok(()),
-        };
+/// Swipe recognized from the motion of the fingers, shared with the other
+/// recognizers
+pub(crate) fn swipe_command(
+    dx: f64,
+    dy: f64,
+    finger_count: i32,
+    swipe: &SwipeConfig,
+    thresholds: &Thresholds,
+) -> Option<InputCommand> {
+    use SwaypedSwipeDir::*;
 
-        self.cmd_desc.send(cmd).await?;
+    let cmd = match swipe_direction(dx, dy, swipe, thresholds)? {
+        Up => InputCommand::SwipeUp(finger_count),
+        Down => InputCommand::SwipeDown(finger_count),
+        Left => InputCommand::SwipeLeft(finger_count),
+        Right => InputCommand::SwipeRight(finger_count),
+        UpLeft => InputCommand::SwipeUpLeft(finger_count),
+        UpRight => InputCommand::SwipeUpRight(finger_count),
+        DownLeft => InputCommand::SwipeDownLeft(finger_count),
+        DownRight => InputCommand::SwipeDownRight(finger_count),
+    };
 
-        Ok(())
-    }
+    Some(cmd)
+}
+
+pub(crate) fn pinch_command(
+    scale: f64,
+    angle: f64,
+    finger_count: i32,
+    pinch: &PinchConfig,
+) -> Option<InputCommand> {
+    use SwaypedPinchDir::*;
+
+    // a twist rarely keeps the fingers at a constant distance, so rotation
+    // wins over scale when both thresholds are reached
+    let dir = if angle >= pinch.rotate_threshold {
+        Cw
+    } else if angle <= -pinch.rotate_threshold {
+        Ccw
+    } else if scale <= pinch.in_threshold {
+        In
+    } else if scale >= pinch.out_threshold {
+        Out
+    } else {
+        return None;
+    };
+
+    let cmd = match dir {
+        In => InputCommand::PinchIn(finger_count),
+        Out => InputCommand::PinchOut(finger_count),
+        Cw => InputCommand::RotateCw(finger_count),
+        Ccw => InputCommand::RotateCcw(finger_count),
+    };
+
+    Some(cmd)
 }
 
 #[cfg(test)]
@@ -118,16 +703,23 @@ mod test {
     use tokio::sync::mpsc;
 
     use super::*;
-    use crate::{commands::CommandDesc, config::TomlConfig};
+    use crate::{
+        commands::CommandDesc,
+        config::{
+            Mapping, RecognitionOverride, SequenceStep, ShapeTemplate, ShapesConfig, TomlConfig,
+            UNITS_PER_MM,
+        },
+        sequence::SwaypedSequence,
+    };
 
     #[test]
     fn test_swayped_gesture_new() {
         let (tx, _) = mpsc::channel(1);
-        let config = TomlConfig { mappings: vec![] };
+        let config = TomlConfig::default();
 
-        let cmd_desc = CommandDesc::new(false, config, tx);
+        let cmd_desc = CommandDesc::new(false, &config, tx);
 
-        let gesture = SwaypedGesture::new(&cmd_desc);
+        let gesture = SwaypedGesture::new(&cmd_desc, &config);
 
         assert_eq!(gesture.dx, 0.0);
         assert_eq!(gesture.dy, 0.0);
@@ -137,86 +729,833 @@ mod test {
     #[tokio::test]
     async fn test_swayped_gesture_reset() {
         let (tx, _) = mpsc::channel(1);
-        let config = TomlConfig { mappings: vec![] };
+        let config = TomlConfig::default();
 
-        let cmd_desc = CommandDesc::new(false, config, tx);
+        let cmd_desc = CommandDesc::new(false, &config, tx);
 
-        let mut gesture = SwaypedGesture::new(&cmd_desc);
+        let mut gesture = SwaypedGesture::new(&cmd_desc, &config);
 
         gesture.dx = 10.0;
         gesture.dy = 10.0;
+        gesture.scale = 0.5;
+        gesture.angle = 90.0;
         gesture.finger_count = 3;
 
         let res = gesture.reset();
-        assert_eq!(res.is_ok(), true);
+        assert!(res.is_ok());
         assert_eq!(gesture.dx, 0.0);
         assert_eq!(gesture.dy, 0.0);
+        assert_eq!(gesture.scale, 1.0);
+        assert_eq!(gesture.angle, 0.0);
         assert_eq!(gesture.finger_count, 0);
     }
 
     #[tokio::test]
     async fn test_swayped_gesture_swipe_process() {
         let (tx, _) = mpsc::channel(1);
-        let config = TomlConfig { mappings: vec![] };
+        let config = TomlConfig::default();
 
-        let cmd_desc = CommandDesc::new(false, config, tx);
+        let cmd_desc = CommandDesc::new(false, &config, tx);
 
-        let mut gesture = SwaypedGesture::new(&cmd_desc);
+        let mut gesture = SwaypedGesture::new(&cmd_desc, &config);
 
         gesture.dx = 100.0;
         gesture.dy = 0.0;
         gesture.finger_count = 3;
 
         let res = gesture.process_swipe().await;
-        assert_eq!(res.is_ok(), true);
+        assert!(res.is_ok());
 
         gesture.dx = 0.0;
         gesture.dy = 100.0;
         gesture.finger_count = 3;
 
         let res = gesture.process_swipe().await;
-        assert_eq!(res.is_ok(), true);
+        assert!(res.is_ok());
 
         gesture.dx = -100.0;
         gesture.dy = 0.0;
         gesture.finger_count = 3;
 
         let res = gesture.process_swipe().await;
-        assert_eq!(res.is_ok(), true);
+        assert!(res.is_ok());
 
         gesture.dx = 0.0;
         gesture.dy = -100.0;
         gesture.finger_count = 3;
 
         let res = gesture.process_swipe().await;
-        assert_eq!(res.is_ok(), true);
+        assert!(res.is_ok());
 
         gesture.dx = 100.0;
         gesture.dy = 100.0;
         gesture.finger_count = 3;
 
         let res = gesture.process_swipe().await;
-        assert_eq!(res.is_ok(), true);
+        assert!(res.is_ok());
 
         gesture.dx = -100.0;
         gesture.dy = 100.0;
         gesture.finger_count = 3;
 
         let res = gesture.process_swipe().await;
-        assert_eq!(res.is_ok(), true);
+        assert!(res.is_ok());
 
         gesture.dx = 100.0;
         gesture.dy = -100.0;
         gesture.finger_count = 3;
 
         let res = gesture.process_swipe().await;
-        assert_eq!(res.is_ok(), true);
+        assert!(res.is_ok());
 
         gesture.dx = -100.0;
         gesture.dy = -100.0;
         gesture.finger_count = 3;
 
         let res = gesture.process_swipe().await;
-        assert_eq!(res.is_ok(), true);
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_swayped_gesture_pinch_process() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig::default();
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut gesture = SwaypedGesture::new(&cmd_desc, &config);
+
+        gesture.scale = 0.5;
+        gesture.finger_count = 2;
+
+        let res = gesture.process_pinch().await;
+        assert!(res.is_ok());
+        assert_eq!(rx.try_recv().unwrap().0, InputCommand::PinchIn(2));
+
+        gesture.scale = 2.0;
+        gesture.finger_count = 3;
+
+        let res = gesture.process_pinch().await;
+        assert!(res.is_ok());
+        assert_eq!(rx.try_recv().unwrap().0, InputCommand::PinchOut(3));
+
+        gesture.scale = 1.1;
+        gesture.finger_count = 2;
+
+        let res = gesture.process_pinch().await;
+        assert!(res.is_ok());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_swayped_gesture_rotate_process() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig::default();
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut gesture = SwaypedGesture::new(&cmd_desc, &config);
+
+        gesture.angle = 60.0;
+        gesture.scale = 0.7;
+        gesture.finger_count = 2;
+
+        let res = gesture.process_pinch().await;
+        assert!(res.is_ok());
+        assert_eq!(rx.try_recv().unwrap().0, InputCommand::RotateCw(2));
+
+        gesture.angle = -60.0;
+        gesture.scale = 1.0;
+        gesture.finger_count = 2;
+
+        let res = gesture.process_pinch().await;
+        assert!(res.is_ok());
+        assert_eq!(rx.try_recv().unwrap().0, InputCommand::RotateCcw(2));
+
+        gesture.angle = 10.0;
+        gesture.scale = 1.0;
+        gesture.finger_count = 2;
+
+        let res = gesture.process_pinch().await;
+        assert!(res.is_ok());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_swayped_gesture_hold_process() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig {
+            mappings: vec![Mapping {
+                gesture: "hold".to_string(),
+                finger_count: Some(3),
+                cmd: "exec fuzzel".to_string(),
+                cmd_type: "sway".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut gesture = SwaypedGesture::new(&cmd_desc, &config);
+
+        gesture.finger_count = 3;
+
+        let res = gesture.process_hold(650).await;
+        assert!(res.is_ok());
+
+        let (cmd, ctx) = rx.try_recv().unwrap();
+        assert_eq!(cmd, InputCommand::Hold(3));
+        assert_eq!(ctx.duration_ms, 650);
+
+        gesture.finger_count = 1;
+
+        let res = gesture.process_hold(200).await;
+        assert!(res.is_ok());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_swayped_gesture_hold_sequence() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let step = |gesture: &str| SequenceStep {
+            gesture: gesture.to_string(),
+            finger_count: Some(3),
+        };
+        let config = TomlConfig {
+            mappings: vec![Mapping {
+                gesture: "sequence".to_string(),
+                sequence: Some(vec![step("hold"), step("swipe_down")]),
+                cmd: "exec fuzzel".to_string(),
+                cmd_type: "sway".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut gesture = SwaypedGesture::new(&cmd_desc, &config);
+        let mut sequence = SwaypedSequence::new();
+
+        gesture.finger_count = 3;
+
+        // only mapped as the first step of the sequence
+        gesture.process_hold(400).await.unwrap();
+        let (cmd, ctx) = rx.try_recv().unwrap();
+        assert!(sequence.push(cmd, ctx, &cmd_desc).is_empty());
+
+        gesture.dy = 150.0;
+        gesture.process_swipe().await.unwrap();
+        let (cmd, ctx) = rx.try_recv().unwrap();
+
+        let ready = sequence.push(cmd, ctx, &cmd_desc);
+        assert_eq!(ready.len(), 1);
+        assert_eq!(
+            ready[0].0,
+            InputCommand::Sequence(vec![InputCommand::Hold(3), InputCommand::SwipeDown(3)])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_swayped_gesture_swipe_diagonal() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig {
+            swipe: SwipeConfig {
+                diagonal_sector: 30.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut gesture = SwaypedGesture::new(&cmd_desc, &config);
+
+        gesture.dx = 150.0;
+        gesture.dy = -140.0;
+        gesture.finger_count = 4;
+
+        let res = gesture.process_swipe().await;
+        assert!(res.is_ok());
+        assert_eq!(rx.try_recv().unwrap().0, InputCommand::SwipeUpRight(4));
+
+        gesture.dx = -150.0;
+        gesture.dy = 160.0;
+
+        let res = gesture.process_swipe().await;
+        assert!(res.is_ok());
+        assert_eq!(rx.try_recv().unwrap().0, InputCommand::SwipeDownLeft(4));
+
+        gesture.dx = -150.0;
+        gesture.dy = 40.0;
+
+        let res = gesture.process_swipe().await;
+        assert!(res.is_ok());
+        assert_eq!(rx.try_recv().unwrap().0, InputCommand::SwipeLeft(4));
+
+        gesture.dx = 50.0;
+        gesture.dy = 50.0;
+
+        let res = gesture.process_swipe().await;
+        assert!(res.is_ok());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_swayped_gesture_swipe_repeat() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig {
+            mappings: vec![Mapping {
+                gesture: "swipe_right".to_string(),
+                finger_count: Some(3),
+                cmd: "workspace next".to_string(),
+                cmd_type: "sway".to_string(),
+                repeat_every: Some(120.0),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut gesture = SwaypedGesture::new(&cmd_desc, &config);
+
+        gesture.dx = 90.0;
+        gesture.finger_count = 3;
+
+        let res = gesture.process_repeat(gesture.timed_context());
+        assert!(res.is_ok());
+        assert!(rx.try_recv().is_err());
+
+        gesture.dx = 250.0;
+
+        let res = gesture.process_repeat(gesture.timed_context());
+        assert!(res.is_ok());
+        assert_eq!(rx.try_recv().unwrap().0, InputCommand::SwipeRight(3));
+
+        let res = gesture.process_repeat(gesture.timed_context());
+        assert!(res.is_ok());
+        assert_eq!(rx.try_recv().unwrap().0, InputCommand::SwipeRight(3));
+
+        let res = gesture.process_repeat(gesture.timed_context());
+        assert!(res.is_ok());
+        assert!(rx.try_recv().is_err());
+        assert_eq!(gesture.repeats, 2);
+
+        // a full channel delays the step instead of blocking
+        gesture.dx = 500.0;
+        cmd_desc.send(InputCommand::SwipeUp(3)).await.unwrap();
+
+        let res = gesture.process_repeat(gesture.timed_context());
+        assert!(res.is_ok());
+        assert_eq!(gesture.repeats, 2);
+        assert_eq!(rx.try_recv().unwrap().0, InputCommand::SwipeUp(3));
+
+        let res = gesture.process_repeat(gesture.timed_context());
+        assert!(res.is_ok());
+        assert_eq!(rx.try_recv().unwrap().0, InputCommand::SwipeRight(3));
+        assert_eq!(gesture.repeats, 3);
+    }
+
+    #[tokio::test]
+    async fn test_swayped_gesture_swipe_analog() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig {
+            mappings: vec![Mapping {
+                gesture: "swipe".to_string(),
+                finger_count: Some(3),
+                cmd: "resize grow width {dx}px".to_string(),
+                cmd_type: "sway".to_string(),
+                interval_ms: Some(50),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut gesture = SwaypedGesture::new(&cmd_desc, &config);
+
+        gesture.finger_count = 3;
+        gesture.analog.dx = 10.4;
+
+        let res = gesture.process_analog_swipe(1_000);
+        assert!(res.is_ok());
+        let (cmd, ctx) = rx.try_recv().unwrap();
+        assert_eq!(cmd, InputCommand::Swipe(3));
+        assert_eq!(ctx.dx, 10.0);
+        assert!(gesture.analog.active);
+
+        gesture.analog.dx += 5.0;
+
+        let res = gesture.process_analog_swipe(20_000);
+        assert!(res.is_ok());
+        assert!(rx.try_recv().is_err());
+
+        let res = gesture.process_analog_swipe(60_000);
+        assert!(res.is_ok());
+        let (_, ctx) = rx.try_recv().unwrap();
+        assert_eq!(ctx.dx, 5.0);
+    }
+
+    #[tokio::test]
+    async fn test_swayped_gesture_swipe_speed() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig::default();
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut gesture = SwaypedGesture::new(&cmd_desc, &config);
+
+        gesture.dx = -300.0;
+        gesture.finger_count = 3;
+        gesture.begin_usec = 1_000_000;
+        gesture.time_usec = 1_080_000;
+
+        let res = gesture.process_swipe().await;
+        assert!(res.is_ok());
+        let (cmd, ctx) = rx.try_recv().unwrap();
+        assert_eq!(cmd, InputCommand::SwipeLeft(3));
+        assert_eq!(ctx.speed, Some(SwipeSpeed::Fast));
+        assert_eq!(ctx.duration_ms, 80);
+
+        gesture.time_usec = 2_000_000;
+
+        let res = gesture.process_swipe().await;
+        assert!(res.is_ok());
+        let (_, ctx) = rx.try_recv().unwrap();
+        assert_eq!(ctx.speed, Some(SwipeSpeed::Slow));
+
+        gesture.time_usec = 1_250_000;
+
+        let res = gesture.process_swipe().await;
+        assert!(res.is_ok());
+        let (_, ctx) = rx.try_recv().unwrap();
+        assert_eq!(ctx.speed, None);
+    }
+
+    #[tokio::test]
+    async fn test_swayped_gesture_thresholds() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig {
+            recognition: RecognitionConfig {
+                threshold: 80.0,
+                overrides: vec![
+                    RecognitionOverride {
+                        finger_count: Some(4),
+                        threshold: Some(200.0),
+                        ..Default::default()
+                    },
+                    RecognitionOverride {
+                        device: Some("Magic Trackpad".to_string()),
+                        threshold: Some(300.0),
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut gesture = SwaypedGesture::new(&cmd_desc, &config);
+
+        gesture.dx = 150.0;
+        gesture.finger_count = 3;
+        gesture.thresholds = config.recognition.thresholds(3, "Touchpad", None);
+
+        let res = gesture.process_swipe().await;
+        assert!(res.is_ok());
+        assert_eq!(rx.try_recv().unwrap().0, InputCommand::SwipeRight(3));
+
+        gesture.finger_count = 4;
+        gesture.thresholds = config.recognition.thresholds(4, "Touchpad", None);
+        assert_eq!(gesture.thresholds.threshold, 200.0);
+
+        let res = gesture.process_swipe().await;
+        assert!(res.is_ok());
+        assert!(rx.try_recv().is_err());
+
+        gesture.thresholds = config.recognition.thresholds(4, "Magic Trackpad", None);
+        assert_eq!(gesture.thresholds.threshold, 300.0);
+    }
+
+    #[test]
+    fn test_swayped_gesture_thresholds_mm() {
+        let recognition = RecognitionConfig {
+            threshold_mm: Some(10.0),
+            unaccelerated: true,
+            overrides: vec![
+                RecognitionOverride {
+                    finger_count: Some(4),
+                    threshold_mm: Some(20.0),
+                    ..Default::default()
+                },
+                RecognitionOverride {
+                    device: Some("Magic Trackpad".to_string()),
+                    threshold: Some(300.0),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let thresholds = recognition.thresholds(3, "Touchpad", None);
+        assert!((thresholds.threshold - 393.7).abs() < 0.1);
+
+        let thresholds = recognition.thresholds(4, "Touchpad", None);
+        assert!((thresholds.threshold - 787.4).abs() < 0.1);
+
+        let thresholds = recognition.thresholds(4, "Magic Trackpad", None);
+        assert_eq!(thresholds.threshold, 300.0);
+
+        // bounded by the short side of the touchpad
+        let thresholds = recognition.thresholds(4, "Touchpad", Some((100.0, 15.0)));
+        assert!((thresholds.threshold - 472.4).abs() < 0.1);
+        let thresholds = recognition.thresholds(4, "Touchpad", Some((100.0, 40.0)));
+        assert!((thresholds.threshold - 787.4).abs() < 0.1);
+
+        // a vertical swipe across most of the short side is still recognized
+        let thresholds = recognition.thresholds(4, "Touchpad", Some((100.0, 15.0)));
+        let swipe = SwipeConfig::default();
+        assert_eq!(
+            swipe_command(0.0, 13.0 * UNITS_PER_MM, 4, &swipe, &thresholds),
+            Some(InputCommand::SwipeDown(4))
+        );
+
+        let accelerated = RecognitionConfig {
+            unaccelerated: false,
+            ..recognition
+        };
+        assert!(accelerated.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_swayped_gesture_threshold_trigger() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig {
+            mappings: vec![Mapping {
+                gesture: "swipe_left".to_string(),
+                finger_count: Some(3),
+                cmd: "workspace prev".to_string(),
+                cmd_type: "sway".to_string(),
+                trigger: Some(Trigger::Threshold),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut gesture = SwaypedGesture::new(&cmd_desc, &config);
+
+        gesture.finger_count = 3;
+        gesture.dx = 150.0;
+
+        let res = gesture
+            .process_threshold(gesture.swipe_command(), gesture.timed_context())
+            .await;
+        assert!(res.is_ok());
+        assert!(rx.try_recv().is_err());
+        assert!(!gesture.triggered);
+
+        gesture.dx = -150.0;
+
+        let res = gesture
+            .process_threshold(gesture.swipe_command(), gesture.timed_context())
+            .await;
+        assert!(res.is_ok());
+        assert_eq!(rx.try_recv().unwrap().0, InputCommand::SwipeLeft(3));
+        assert!(gesture.triggered);
+    }
+
+    #[tokio::test]
+    async fn test_swayped_gesture_threshold_trigger_speed() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig {
+            mappings: vec![Mapping {
+                gesture: "swipe_left".to_string(),
+                finger_count: Some(3),
+                cmd: "move container to workspace prev".to_string(),
+                cmd_type: "sway".to_string(),
+                trigger: Some(Trigger::Threshold),
+                speed: Some(SwipeSpeed::Fast),
+                ..Default::default()
+            }],
+            recognition: RecognitionConfig {
+                min_duration_ms: 60,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(true, &config, tx);
+
+        let mut gesture = SwaypedGesture::new(&cmd_desc, &config);
+
+        gesture.finger_count = 3;
+        gesture.begin_usec = 1_000_000;
+        gesture.time_usec = 1_030_000;
+        gesture.update_filters(3);
+
+        // too short yet
+        gesture.dx = -150.0;
+        let ctx = CommandContext {
+            speed: gesture.swipe_speed(),
+            ..gesture.timed_context()
+        };
+        let res = gesture
+            .process_threshold(gesture.swipe_command(), ctx)
+            .await;
+        assert!(res.is_ok());
+        assert!(rx.try_recv().is_err());
+        assert!(!gesture.triggered);
+
+        gesture.dx = -300.0;
+        gesture.time_usec = 1_080_000;
+        let ctx = CommandContext {
+            speed: gesture.swipe_speed(),
+            ..gesture.timed_context()
+        };
+        let res = gesture
+            .process_threshold(gesture.swipe_command(), ctx)
+            .await;
+        assert!(res.is_ok());
+        assert!(gesture.triggered);
+
+        let (cmd, ctx) = rx.try_recv().unwrap();
+        assert_eq!(cmd, InputCommand::SwipeLeft(3));
+        assert_eq!(ctx.duration_ms, 80);
+        assert_eq!(ctx.speed, Some(SwipeSpeed::Fast));
+        // dry run, only the lookup of the mapping
+        assert!(cmd.process_command(&ctx, &cmd_desc).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_swayped_gesture_analog_discrete() {
+        let (tx, mut rx) = mpsc::channel(4);
+        let config = TomlConfig {
+            mappings: vec![
+                Mapping {
+                    gesture: "swipe".to_string(),
+                    finger_count: Some(3),
+                    cmd: "resize grow height {dy}px".to_string(),
+                    cmd_type: "sway".to_string(),
+                    interval_ms: Some(50),
+                    ..Default::default()
+                },
+                Mapping {
+                    gesture: "swipe_down".to_string(),
+                    finger_count: Some(3),
+                    cmd: "workspace next".to_string(),
+                    cmd_type: "sway".to_string(),
+                    trigger: Some(Trigger::Threshold),
+                    ..Default::default()
+                },
+                Mapping {
+                    gesture: "swipe_down".to_string(),
+                    finger_count: Some(4),
+                    cmd: "focus down".to_string(),
+                    cmd_type: "sway".to_string(),
+                    repeat_every: Some(120.0),
+                    min_duration_ms: Some(100),
+                    ..Default::default()
+                },
+            ],
+            recognition: RecognitionConfig {
+                min_duration_ms: 0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(true, &config, tx);
+
+        let mut gesture = SwaypedGesture::new(&cmd_desc, &config);
+
+        // the analog mapping takes over, no discrete command on the way
+        gesture.finger_count = 3;
+        gesture.begin_usec = 1_000_000;
+        gesture
+            .process_motion(0.0, 300.0, 3, 1_100_000)
+            .await
+            .unwrap();
+        assert_eq!(rx.try_recv().unwrap().0, InputCommand::Swipe(3));
+        assert!(rx.try_recv().is_err());
+        assert!(!gesture.triggered);
+
+        // repeats carry the duration of the gesture for the mapping to match
+        gesture.reset().unwrap();
+        gesture.finger_count = 4;
+        gesture.begin_usec = 1_000_000;
+        gesture
+            .process_motion(0.0, 150.0, 4, 1_050_000)
+            .await
+            .unwrap();
+        let (cmd, ctx) = rx.try_recv().unwrap();
+        assert_eq!(cmd, InputCommand::SwipeDown(4));
+        assert_eq!(ctx.duration_ms, 50);
+        assert!(cmd.process_command(&ctx, &cmd_desc).is_err());
+
+        gesture
+            .process_motion(0.0, 150.0, 4, 1_150_000)
+            .await
+            .unwrap();
+        let (cmd, ctx) = rx.try_recv().unwrap();
+        assert_eq!(ctx.duration_ms, 150);
+        assert!(cmd.process_command(&ctx, &cmd_desc).is_ok());
+    }
+
+    #[test]
+    fn test_swayped_gesture_reversed() {
+        let (tx, _) = mpsc::channel(1);
+        let config = TomlConfig::default();
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut gesture = SwaypedGesture::new(&cmd_desc, &config);
+
+        gesture.dx = 150.0;
+        gesture.peak = 400.0;
+        assert!(!gesture.reversed());
+
+        gesture.recognition.reverse_cancel = Some(0.5);
+        assert!(gesture.reversed());
+
+        gesture.dx = 250.0;
+        assert!(!gesture.reversed());
+    }
+
+    #[test]
+    fn test_swayped_gesture_filters() {
+        let (tx, _) = mpsc::channel(1);
+        let config = TomlConfig {
+            recognition: RecognitionConfig {
+                min_duration_ms: 50,
+                max_duration_ms: Some(1000),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut gesture = SwaypedGesture::new(&cmd_desc, &config);
+
+        gesture.finger_count = 3;
+        gesture.begin_usec = 1_000_000;
+        gesture.time_usec = 1_020_000;
+        assert!(!gesture.accepted());
+
+        gesture.time_usec = 1_200_000;
+        gesture.update_filters(3);
+        assert!(gesture.accepted());
+
+        gesture.update_filters(4);
+        assert!(!gesture.accepted());
+        assert_eq!(gesture.finger_count, 3);
+
+        gesture.reset().unwrap();
+        gesture.finger_count = 3;
+        gesture.begin_usec = 1_000_000;
+        gesture.time_usec = 2_500_000;
+        gesture.update_filters(3);
+        assert!(gesture.rejected);
+        assert!(!gesture.accepted());
+
+        gesture.recognition.finger_change = FingerChange::Reclassify;
+        gesture.reset().unwrap();
+        gesture.finger_count = 3;
+        gesture.begin_usec = 1_000_000;
+        gesture.time_usec = 1_200_000;
+        gesture.update_filters(4);
+        assert!(gesture.accepted());
+        assert_eq!(gesture.finger_count, 4);
+    }
+
+    #[tokio::test]
+    async fn test_swayped_gesture_shape() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig {
+            mappings: vec![Mapping {
+                gesture: "shape".to_string(),
+                shape: Some("L".to_string()),
+                finger_count: Some(3),
+                cmd: "exec foot".to_string(),
+                cmd_type: "sway".to_string(),
+                ..Default::default()
+            }],
+            shapes: ShapesConfig {
+                templates: vec![ShapeTemplate {
+                    name: "L".to_string(),
+                    points: vec![(0.0, 0.0), (0.0, 100.0), (60.0, 100.0)],
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut gesture = SwaypedGesture::new(&cmd_desc, &config);
+
+        gesture.path = vec![(0.0, 0.0), (5.0, 150.0), (0.0, 300.0), (180.0, 300.0)];
+        gesture.dx = 180.0;
+        gesture.dy = 300.0;
+        gesture.finger_count = 3;
+
+        let res = gesture.process_swipe().await;
+        assert!(res.is_ok());
+        assert_eq!(
+            rx.try_recv().unwrap().0,
+            InputCommand::Shape("L".to_string(), 3)
+        );
+
+        // a straight swipe is still a swipe
+        gesture.path = vec![(0.0, 0.0), (0.0, 300.0)];
+        gesture.dx = 0.0;
+        gesture.dy = 300.0;
+
+        let res = gesture.process_swipe().await;
+        assert!(res.is_ok());
+        assert_eq!(rx.try_recv().unwrap().0, InputCommand::SwipeDown(3));
+
+        // shapes are only matched for the mapped finger count
+        gesture.path = vec![(0.0, 0.0), (5.0, 150.0), (0.0, 300.0), (180.0, 300.0)];
+        gesture.dx = 180.0;
+        gesture.dy = 300.0;
+        gesture.finger_count = 4;
+
+        let res = gesture.process_swipe().await;
+        assert!(res.is_ok());
+        assert_ne!(
+            rx.try_recv().unwrap().0,
+            InputCommand::Shape("L".to_string(), 4)
+        );
+    }
+
+    #[test]
+    fn test_swayped_gesture_typing() {
+        let (tx, _) = mpsc::channel(1);
+        let mut config = TomlConfig::default();
+        config.recognition.typing_timeout_ms = Some(500);
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+        cmd_desc.set_last_key(1_000_000);
+
+        let mut gesture = SwaypedGesture::new(&cmd_desc, &config);
+
+        gesture.begin_usec = 1_300_000;
+        gesture.time_usec = 1_600_000;
+        gesture.typing_filter();
+        assert!(!gesture.accepted());
+
+        gesture.reset().unwrap();
+        gesture.begin_usec = 1_600_000;
+        gesture.time_usec = 1_900_000;
+        gesture.typing_filter();
+        assert!(gesture.accepted());
     }
 }
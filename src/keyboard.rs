@@ -0,0 +1,148 @@
+use std::collections::HashSet;
+
+use input::event::keyboard::{KeyState, KeyboardEventTrait, KeyboardKeyEvent};
+use tracing::trace;
+
+use crate::commands::CommandDesc;
+use crate::config::Modifier;
+
+/// Linux input event codes of the modifier keys, left and right
+const MODIFIER_KEYS: [(u32, Modifier); 8] = [
+    (42, Modifier::Shift),
+    (54, Modifier::Shift),
+    (29, Modifier::Ctrl),
+    (97, Modifier::Ctrl),
+    (56, Modifier::Alt),
+    (100, Modifier::Alt),
+    (125, Modifier::Super),
+    (126, Modifier::Super),
+];
+
+/// Set of modifiers held down on any keyboard
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    pub fn new(modifiers: &[Modifier]) -> Self {
+        Modifiers(modifiers.iter().fold(0, |acc, x| acc | 1 << *x as u8))
+    }
+}
+
+/// Keeps track of the keys held down across all the keyboards of the seat
+pub struct SwaypedKeyboard<'a> {
+    pressed: HashSet<u32>,
+    cmd_desc: &'a CommandDesc,
+}
+
+impl<'a> SwaypedKeyboard<'a> {
+    pub fn new(cmd_desc: &'a CommandDesc) -> Self {
+        SwaypedKeyboard {
+            pressed: HashSet::new(),
+            cmd_desc,
+        }
+    }
+
+    /// Release everything, when a keyboard goes away with keys held down
+    pub fn reset(&mut self) {
+        self.pressed.clear();
+        self.cmd_desc.set_modifiers(Modifiers::default());
+    }
+
+    pub fn handle_key_event(&mut self, event: &KeyboardKeyEvent) {
+        self.key(event.key(), event.key_state(), event.time_usec());
+    }
+
+    fn key(&mut self, key: u32, state: KeyState, usec: u64) {
+        let Some(&(_, modifier)) = MODIFIER_KEYS.iter().find(|(code, _)| *code == key) else {
+            if state == KeyState::Pressed {
+                self.cmd_desc.set_last_key(usec);
+            }
+            return;
+        };
+
+        match state {
+            KeyState::Pressed => self.pressed.insert(key),
+            KeyState::Released => self.pressed.remove(&key),
+        };
+
+        let held: Vec<Modifier> = MODIFIER_KEYS
+            .iter()
+            .filter(|(code, _)| self.pressed.contains(code))
+            .map(|(_, x)| *x)
+            .collect();
+        trace!(?modifier, ?state, ?held, "modifiers");
+        self.cmd_desc.set_modifiers(Modifiers::new(&held));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::commands::{CommandContext, InputCommand};
+    use crate::config::TomlConfig;
+
+    #[tokio::test]
+    async fn test_swayped_keyboard_modifiers() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig::default();
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut keyboard = SwaypedKeyboard::new(&cmd_desc);
+
+        keyboard.key(125, KeyState::Pressed, 0);
+        keyboard.key(30, KeyState::Pressed, 0);
+        keyboard.key(42, KeyState::Pressed, 0);
+        keyboard.key(54, KeyState::Pressed, 0);
+        keyboard.key(42, KeyState::Released, 0);
+
+        cmd_desc
+            .send_with_context(InputCommand::SwipeLeft(3), CommandContext::default())
+            .await
+            .unwrap();
+        let (_, ctx) = rx.try_recv().unwrap();
+        assert_eq!(
+            ctx.modifiers,
+            Modifiers::new(&[Modifier::Shift, Modifier::Super])
+        );
+
+        keyboard.key(125, KeyState::Released, 0);
+        keyboard.key(54, KeyState::Released, 0);
+
+        cmd_desc.send(InputCommand::SwipeLeft(3)).await.unwrap();
+        let (_, ctx) = rx.try_recv().unwrap();
+        assert_eq!(ctx.modifiers, Modifiers::default());
+
+        // unplugged while held
+        keyboard.key(29, KeyState::Pressed, 0);
+        keyboard.reset();
+        keyboard.key(42, KeyState::Pressed, 0);
+
+        cmd_desc.send(InputCommand::SwipeLeft(3)).await.unwrap();
+        let (_, ctx) = rx.try_recv().unwrap();
+        assert_eq!(ctx.modifiers, Modifiers::new(&[Modifier::Shift]));
+    }
+
+    #[test]
+    fn test_swayped_keyboard_typing() {
+        let (tx, _) = mpsc::channel(1);
+        let mut config = TomlConfig::default();
+        config.recognition.typing_timeout_ms = Some(500);
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut keyboard = SwaypedKeyboard::new(&cmd_desc);
+        assert!(!cmd_desc.typing(1_000_000));
+
+        // modifiers are held along with gestures, they are not typing
+        keyboard.key(125, KeyState::Pressed, 1_000_000);
+        assert!(!cmd_desc.typing(1_100_000));
+
+        keyboard.key(30, KeyState::Pressed, 1_000_000);
+        keyboard.key(30, KeyState::Released, 1_050_000);
+        assert!(cmd_desc.typing(1_100_000));
+        assert!(!cmd_desc.typing(1_500_000));
+    }
+}
@@ -1,19 +1,41 @@
 mod commands;
 mod config;
+mod device;
 mod gesture;
+mod keyboard;
+mod mouse;
+mod pad;
 mod pointer;
+mod sequence;
+mod shape;
+mod switch;
+mod touch;
 
 use anyhow::bail;
 use anyhow::Context;
 use anyhow::Result;
+use device::{DeviceInfo, SwaypedDevices};
 use gesture::SwaypedGesture;
+use input::event::gesture::{GestureEndEvent, GestureEventCoordinates, GestureSwipeEvent};
 use input::event::Event::Gesture;
+use input::event::Event::Keyboard;
 use input::event::Event::Pointer;
-use input::event::GestureEvent::{Hold, Swipe};
-use input::event::PointerEvent::ScrollWheel;
-use input::{Event, Libinput, LibinputInterface};
+use input::event::Event::Switch;
+use input::event::Event::TabletPad;
+use input::event::Event::Touch;
+use input::event::EventTrait;
+use input::event::GestureEvent::{Hold, Pinch, Swipe};
+use input::event::KeyboardEvent::Key;
+use input::event::PointerEvent::{Button, Motion, ScrollFinger, ScrollWheel};
+use input::event::SwitchEvent::Toggle;
+use input::event::TabletPadEvent::{Button as PadButton, Ring, Strip};
+use input::{DeviceCapability, Event, Libinput, LibinputInterface};
+use keyboard::SwaypedKeyboard;
 use libc::{O_RDWR, O_WRONLY};
-use tracing::warn;
+use mouse::SwaypedMouse;
+use pad::SwaypedPad;
+use pointer::SwaypedPointer;
+use sequence::SwaypedSequence;
 use std::fs::{File, OpenOptions};
 use std::io;
 use std::os::unix::{
@@ -22,18 +44,21 @@ use std::os::unix::{
 };
 use std::path::Path;
 use std::path::PathBuf;
+use switch::SwaypedSwitch;
 use tokio::io::unix::AsyncFd;
 use tokio::io::Ready;
 use tokio::select;
 use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::mpsc;
+use tokio::time::{sleep_until, Instant};
+use touch::{watch_output, SwaypedTouch};
 use tracing::error;
 use tracing::info;
 use tracing::trace;
+use tracing::warn;
 
-use crate::commands::{CommandDesc, InputCommand};
+use crate::commands::{CommandContext, CommandDesc, InputCommand};
 use crate::config::TomlConfig;
-use crate::pointer::pointer_handle_scroll_event;
 
 struct Interface;
 
@@ -79,36 +104,117 @@ impl AsyncLibinput {
     }
 }
 
-async fn process_event(
-    event: &Event,
-    gesture: &mut Box<SwaypedGesture<'_>>,
-    cmd_desc: &CommandDesc,
-) {
-    trace!(?event, "Processing event:");
-    let res = match event {
-        Gesture(Hold(_)) => gesture.reset(),
-        Gesture(Swipe(event)) => gesture.handle_event(event).await,
-        Pointer(ScrollWheel(event)) => pointer_handle_scroll_event(event, cmd_desc).await,
-        _ => Ok(()),
-    };
+/// Recognizers the libinput events are dispatched to, unless the device
+/// they come from is excluded
+struct SwaypedRecognizers<'a> {
+    devices: SwaypedDevices,
+    gesture: Box<SwaypedGesture<'a>>,
+    touch: SwaypedTouch<'a>,
+    pointer: SwaypedPointer<'a>,
+    mouse: SwaypedMouse<'a>,
+    keyboard: SwaypedKeyboard<'a>,
+    switch: SwaypedSwitch<'a>,
+    pad: SwaypedPad<'a>,
+}
 
-    if let Err(err) = res {
-        error!(?err, "Error");
+impl<'a> SwaypedRecognizers<'a> {
+    fn new(cmd_desc: &'a CommandDesc, config: &TomlConfig) -> Self {
+        SwaypedRecognizers {
+            devices: SwaypedDevices::new(config),
+            gesture: Box::new(SwaypedGesture::new(cmd_desc, config)),
+            touch: SwaypedTouch::new(cmd_desc, config),
+            pointer: SwaypedPointer::new(cmd_desc, config),
+            mouse: SwaypedMouse::new(cmd_desc, config),
+            keyboard: SwaypedKeyboard::new(cmd_desc),
+            switch: SwaypedSwitch::new(cmd_desc),
+            pad: SwaypedPad::new(cmd_desc, config),
+        }
     }
-}
 
-pub async fn run(dry_run: bool, config_file: Option<String>) -> Result<()> {
-    let mut sigterm = signal(SignalKind::terminate()).context("Failed to create SIGTERM signal")?;
+    /// Forget the keys, buttons, touches and gestures held on a device that
+    /// went away, none of them will be released
+    fn device_removed(&mut self, info: &DeviceInfo) {
+        for capability in &info.capabilities {
+            match capability {
+                DeviceCapability::Keyboard => self.keyboard.reset(),
+                DeviceCapability::Pointer => {
+                    self.pointer.reset();
+                    self.mouse.reset();
+                }
+                DeviceCapability::Touch => self.touch.reset(),
+                DeviceCapability::Gesture => {
+                    self.gesture.reset().ok();
+                }
+                DeviceCapability::TabletPad => self.pad.reset(),
+                _ => (),
+            }
+        }
+    }
 
-    let mut sigint = signal(SignalKind::interrupt()).context("Failed to create SIGINT signal")?;
+    async fn process_event(&mut self, event: &Event) {
+        trace!(?event, "Processing event:");
+        if let Event::Device(event) = event {
+            if let Some(info) = self.devices.handle_event(event) {
+                self.device_removed(&info);
+            }
+            return;
+        }
 
+        if !self.devices.allowed(&event.device()) {
+            trace!("Ignoring event of an excluded device");
+            return;
+        }
+
+        let res = match event {
+            Gesture(Hold(event)) => self.gesture.handle_hold_event(event).await,
+            Gesture(Swipe(event)) => self.gesture.handle_event(event).await,
+            Gesture(Pinch(event)) => self.gesture.handle_pinch_event(event).await,
+            Keyboard(Key(event)) => {
+                self.keyboard.handle_key_event(event);
+                Ok(())
+            }
+            Touch(event) => self.touch.handle_event(event).await,
+            Pointer(Button(event)) => match self.mouse.handle_button_event(event).await {
+                Ok(()) => self.pointer.handle_button_event(event).await,
+                err => err,
+            },
+            Pointer(Motion(event)) => {
+                self.mouse.handle_motion_event(event);
+                Ok(())
+            }
+            Pointer(ScrollWheel(event)) => self.pointer.handle_scroll_event(event).await,
+            Pointer(ScrollFinger(event)) => self.pointer.handle_scroll_finger_event(event).await,
+            Switch(Toggle(event)) => self.switch.handle_toggle_event(event).await,
+            TabletPad(PadButton(event)) => self.pad.handle_button_event(event).await,
+            TabletPad(Ring(event)) => self.pad.handle_ring_event(event).await,
+            TabletPad(Strip(event)) => self.pad.handle_strip_event(event).await,
+            _ => Ok(()),
+        };
+
+        if let Err(err) = res {
+            error!(?err, "Error");
+        }
+    }
+}
+
+fn process_command(cmd: InputCommand, ctx: &CommandContext, cmd_desc: &CommandDesc) {
+    cmd.process_command(ctx, cmd_desc).unwrap_or_else(|err| {
+        warn!(?err, "Failed to process command");
+    });
+}
+
+fn open_input() -> Result<AsyncLibinput> {
     let mut input = Libinput::new_with_udev(Interface);
     let Ok(_) = input.udev_assign_seat("seat0") else {
         bail!("Failed to assign seat");
     };
 
-    let mut input = AsyncLibinput(AsyncFd::new(input).context("Failed to create async libinput")?);
+    Ok(AsyncLibinput(
+        AsyncFd::new(input).context("Failed to create async libinput")?,
+    ))
+}
 
+fn load_config(config_file: Option<String>) -> Result<TomlConfig> {
     let config_file = match config_file {
         Some(file) => PathBuf::from(file),
         None => TomlConfig::config_dir().join("config.toml"),
@@ -116,12 +222,64 @@ pub async fn run(dry_run: bool, config_file: Option<String>) -> Result<()> {
 
     info!(?config_file, "Starting swayped");
 
-    let config = TomlConfig::new(config_file)?;
+    TomlConfig::new(config_file)
+}
+
+/// Record a single swipe and print it as a shape template for the config
+pub async fn train(name: String, config_file: Option<String>) -> Result<()> {
+    let mut input = open_input()?;
+    let config = load_config(config_file)?;
+    let unaccelerated = config.recognition.unaccelerated;
 
-    let (tx, mut rx) = mpsc::channel::<InputCommand>(8);
-    let command_desc = CommandDesc::new(dry_run, config, tx);
+    info!(?name, "Draw the shape with a swipe");
 
-    let mut gesture = Box::new(SwaypedGesture::new(&command_desc));
+    let mut path = vec![];
+    let mut events = Vec::new();
+    loop {
+        events.clear();
+        if input.read(&mut events).await.is_err() {
+            continue;
+        }
+        for event in &events {
+            match event {
+                Gesture(Swipe(GestureSwipeEvent::Begin(_))) => path = vec![(0.0, 0.0)],
+                Gesture(Swipe(GestureSwipeEvent::Update(event))) => {
+                    let (x, y) = path.last().copied().unwrap_or_default();
+                    let (dx, dy) = if unaccelerated {
+                        (event.dx_unaccelerated(), event.dy_unaccelerated())
+                    } else {
+                        (event.dx(), event.dy())
+                    };
+                    path.push((x + dx, y + dy));
+                }
+                Gesture(Swipe(GestureSwipeEvent::End(event))) => {
+                    if event.cancelled() || shape::path_length(&path) <= 0.0 {
+                        warn!("Swipe cancelled, draw the shape again");
+                        continue;
+                    }
+                    println!("{}", shape::template(&name, &path));
+                    return Ok(());
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+pub async fn run(dry_run: bool, config_file: Option<String>) -> Result<()> {
+    let mut sigterm = signal(SignalKind::terminate()).context("Failed to create SIGTERM signal")?;
+
+    let mut sigint = signal(SignalKind::interrupt()).context("Failed to create SIGINT signal")?;
+
+    let mut input = open_input()?;
+    let config = load_config(config_file)?;
+
+    let (tx, mut rx) = mpsc::channel::<(InputCommand, CommandContext)>(8);
+    let command_desc = CommandDesc::new(dry_run, &config, tx);
+
+    let mut recognizers = SwaypedRecognizers::new(&command_desc, &config);
+    let mut output = watch_output(&command_desc, &config);
+    let mut sequence = SwaypedSequence::new();
     let mut events = Vec::new();
 
     loop {
@@ -129,14 +287,32 @@ pub async fn run(dry_run: bool, config_file: Option<String>) -> Result<()> {
         select! {
             Ok(_) = input.read(&mut events) => {
                 for event in &events {
-                    process_event(event, &mut gesture, &command_desc).await;
+                    recognizers.process_event(event).await;
+
+                    // recognizers block on a full channel, which only this
+                    // task drains, run what each event queued before the next
+                    while let Ok((cmd, ctx)) = rx.try_recv() {
+                        for (cmd, ctx) in sequence.push(cmd, ctx, &command_desc) {
+                            process_command(cmd, &ctx, &command_desc);
+                        }
+                    }
+                }
+            },
+
+            Some((cmd, ctx)) = rx.recv() => {
+                for (cmd, ctx) in sequence.push(cmd, ctx, &command_desc) {
+                    process_command(cmd, &ctx, &command_desc);
                 }
             },
 
-            Some(cmd) = rx.recv() => {
-                cmd.process_command(&command_desc).unwrap_or_else(|err| {
-                    warn!(?err, "Failed to process command");
-                });
+            Ok(()) = output.changed() => {
+                recognizers.touch.set_output(output.borrow_and_update().clone());
+            },
+
+            _ = sleep_until(sequence.deadline().unwrap_or_else(Instant::now)), if sequence.deadline().is_some() => {
+                for (cmd, ctx) in sequence.timeout(&command_desc) {
+                    process_command(cmd, &ctx, &command_desc);
+                }
             },
 
             _ = sigterm.recv() => {
@@ -156,3 +332,40 @@ pub async fn run(dry_run: bool, config_file: Option<String>) -> Result<()> {
     info!("Terminating program");
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Modifier;
+    use crate::keyboard::Modifiers;
+
+    #[tokio::test]
+    async fn test_swayped_recognizers_device_removed() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig::default();
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut recognizers = SwaypedRecognizers::new(&cmd_desc, &config);
+
+        cmd_desc.set_modifiers(Modifiers::new(&[Modifier::Super]));
+        let mut info = DeviceInfo {
+            name: "Logitech USB Receiver".to_string(),
+            vendor: 0x046d,
+            product: 0xc52b,
+            capabilities: vec![DeviceCapability::Pointer],
+        };
+
+        recognizers.device_removed(&info);
+        cmd_desc.send(InputCommand::SwipeLeft(3)).await.unwrap();
+        let (_, ctx) = rx.try_recv().unwrap();
+        assert_eq!(ctx.modifiers, Modifiers::new(&[Modifier::Super]));
+
+        // the keyboard went away with Super held
+        info.capabilities.push(DeviceCapability::Keyboard);
+        recognizers.device_removed(&info);
+        cmd_desc.send(InputCommand::SwipeLeft(3)).await.unwrap();
+        let (_, ctx) = rx.try_recv().unwrap();
+        assert_eq!(ctx.modifiers, Modifiers::default());
+    }
+}
@@ -1,6 +1,6 @@
 use anyhow::Context;
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use syslog_tracing::Syslog;
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::prelude::*;
@@ -25,6 +25,18 @@ struct Args {
     #[arg(env = "SWAYPED_LOG")]
     #[arg(default_value = "info")]
     pub log_level: String,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Record a shape drawn with a swipe and print its configuration
+    Train {
+        /// Shape name, as used by the mappings
+        name: String,
+    },
 }
 
 pub fn setup_logging(log_level: &str, syslog: bool) -> Result<()> {
@@ -34,7 +46,7 @@ pub fn setup_logging(log_level: &str, syslog: bool) -> Result<()> {
         .add_directive(log_level.parse()?);
 
     if syslog {
-        let identity = std::ffi::CStr::from_bytes_with_nul(b"swayped\0").unwrap();
+        let identity = c"swayped";
         let (options, facility) = Default::default();
         let syslog = Syslog::new(identity, options, facility).unwrap();
         let layer = tracing_subscriber::fmt::layer().with_writer(syslog);
@@ -53,6 +65,9 @@ pub fn setup_logging(log_level: &str, syslog: bool) -> Result<()> {
 async fn main() -> Result<()> {
     let args = Args::parse();
     setup_logging(&args.log_level, args.syslog)?;
-    swayped::run(args.dry_run, args.config_file).await?;
+    match args.command {
+        Some(Command::Train { name }) => swayped::train(name, args.config_file).await?,
+        None => swayped::run(args.dry_run, args.config_file).await?,
+    }
     Ok(())
 }
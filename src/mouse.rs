@@ -0,0 +1,193 @@
+use anyhow::Result;
+use input::event::pointer::{ButtonState, PointerButtonEvent, PointerMotionEvent};
+use tracing::{debug, trace, warn};
+
+use crate::commands::{CommandDesc, InputCommand};
+use crate::config::{SwipeConfig, Thresholds, TomlConfig};
+use crate::gesture::swipe_command;
+use crate::pointer::button_code;
+use crate::shape::{Point, ShapeRecognizer};
+
+/// Strokes drawn with the pointer while the gesture button is held, mapped
+/// as swipes and shapes without a finger count
+pub struct SwaypedMouse<'a> {
+    button: Option<u32>,
+    pressed: bool,
+    dx: f64,
+    dy: f64,
+    path: Vec<Point>,
+    unaccelerated: bool,
+    swipe: SwipeConfig,
+    thresholds: Thresholds,
+    shapes: ShapeRecognizer,
+    cmd_desc: &'a CommandDesc,
+}
+
+impl<'a> SwaypedMouse<'a> {
+    pub fn new(cmd_desc: &'a CommandDesc, config: &TomlConfig) -> Self {
+        let button = button_code(&config.mouse.button);
+        if button.is_none() {
+            warn!(button = ?config.mouse.button, "Unsupported mouse gesture button");
+        }
+
+        SwaypedMouse {
+            button,
+            pressed: false,
+            dx: 0.0,
+            dy: 0.0,
+            path: vec![(0.0, 0.0)],
+            unaccelerated: config.recognition.unaccelerated,
+            swipe: config.swipe.clone(),
+            thresholds: Thresholds {
+                threshold: config.mouse.threshold,
+                axis_ratio: config.recognition.axis_ratio,
+            },
+            shapes: ShapeRecognizer::new(&config.shapes),
+            cmd_desc,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.pressed = false;
+        self.dx = 0.0;
+        self.dy = 0.0;
+        self.path = vec![(0.0, 0.0)];
+    }
+
+    pub async fn handle_button_event(&mut self, event: &PointerButtonEvent) -> Result<()> {
+        if Some(event.button()) != self.button || !self.cmd_desc.has_mouse() {
+            return Ok(());
+        }
+
+        match event.button_state() {
+            ButtonState::Pressed => {
+                self.reset();
+                self.pressed = true;
+            }
+            ButtonState::Released if self.pressed => {
+                self.terminate().await?;
+                self.reset();
+            }
+            ButtonState::Released => (),
+        }
+        Ok(())
+    }
+
+    pub fn handle_motion_event(&mut self, event: &PointerMotionEvent) {
+        if !self.pressed {
+            return;
+        }
+
+        if self.unaccelerated {
+            self.motion(event.dx_unaccelerated(), event.dy_unaccelerated());
+        } else {
+            self.motion(event.dx(), event.dy());
+        }
+    }
+
+    fn motion(&mut self, dx: f64, dy: f64) {
+        self.dx += dx;
+        self.dy += dy;
+        self.path.push((self.dx, self.dy));
+    }
+
+    /// Shape drawn by the stroke, falling back to its direction
+    fn stroke_command(&self) -> Option<InputCommand> {
+        let shape = self
+            .shapes
+            .recognize(&self.path)
+            .map(|(name, _)| {
+                InputCommand::Mouse(Box::new(InputCommand::Shape(name.to_string(), 0)))
+            })
+            .filter(|cmd| self.cmd_desc.contains(cmd));
+
+        shape.or_else(|| {
+            let swipe = swipe_command(self.dx, self.dy, 0, &self.swipe, &self.thresholds)?;
+            Some(InputCommand::Mouse(Box::new(swipe)))
+        })
+    }
+
+    async fn terminate(&mut self) -> Result<()> {
+        trace!(?self.dx, ?self.dy, points = ?self.path.len(), "terminate stroke");
+        let stroke = self.stroke_command();
+
+        debug!(?stroke, "stroke_process");
+
+        let Some(cmd) = stroke else {
+            return Ok(());
+        };
+
+        self.cmd_desc.send(cmd).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::config::{Mapping, ShapeTemplate, ShapesConfig};
+
+    #[tokio::test]
+    async fn test_swayped_mouse_stroke() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig {
+            mappings: vec![
+                Mapping {
+                    gesture: "mouse_left".to_string(),
+                    cmd: "workspace prev".to_string(),
+                    cmd_type: "sway".to_string(),
+                    ..Default::default()
+                },
+                Mapping {
+                    gesture: "mouse_shape".to_string(),
+                    shape: Some("L".to_string()),
+                    cmd: "exec foot".to_string(),
+                    cmd_type: "sway".to_string(),
+                    ..Default::default()
+                },
+            ],
+            shapes: ShapesConfig {
+                templates: vec![ShapeTemplate {
+                    name: "L".to_string(),
+                    points: vec![(0.0, 0.0), (0.0, 100.0), (60.0, 100.0)],
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut mouse = SwaypedMouse::new(&cmd_desc, &config);
+        assert_eq!(mouse.button, Some(0x111));
+
+        for _ in 0..10 {
+            mouse.motion(-20.0, 1.0);
+        }
+        mouse.terminate().await.unwrap();
+        assert_eq!(
+            rx.try_recv().unwrap().0,
+            InputCommand::Mouse(Box::new(InputCommand::SwipeLeft(0)))
+        );
+
+        mouse.reset();
+        for _ in 0..10 {
+            mouse.motion(0.0, 30.0);
+        }
+        for _ in 0..10 {
+            mouse.motion(20.0, 0.0);
+        }
+        mouse.terminate().await.unwrap();
+        assert_eq!(
+            rx.try_recv().unwrap().0,
+            InputCommand::Mouse(Box::new(InputCommand::Shape("L".to_string(), 0)))
+        );
+
+        // a click without moving
+        mouse.reset();
+        mouse.terminate().await.unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use input::event::tablet_pad::{
+    ButtonState, TabletPadButtonEvent, TabletPadEventTrait, TabletPadRingEvent, TabletPadStripEvent,
+};
+use tracing::{debug, trace, warn};
+
+use crate::commands::{CommandContext, CommandDesc, InputCommand};
+use crate::config::{ButtonAction, PadConfig, TomlConfig};
+
+/// Position of a ring or strip and the travel not sent yet
+#[derive(Debug, Default)]
+struct Axis {
+    position: Option<f64>,
+    travel: f64,
+}
+
+impl Axis {
+    /// Moves to `position`, returns the whole steps travelled, negative going
+    /// backward. Rings wrap around after `period`
+    fn update(&mut self, position: f64, step: f64, period: Option<f64>) -> i64 {
+        // libinput sends -1 when the finger is lifted
+        if position < 0.0 {
+            *self = Axis::default();
+            return 0;
+        }
+
+        let Some(last) = self.position.replace(position) else {
+            return 0;
+        };
+        let mut delta = position - last;
+        if let Some(period) = period {
+            delta = (delta + period / 2.0).rem_euclid(period) - period / 2.0;
+        }
+
+        self.travel += delta;
+        let steps = (self.travel / step).trunc();
+        self.travel -= steps * step;
+        steps as i64
+    }
+}
+
+/// Buttons, rings and strips of tablet pads, sent along with the mode of
+/// their mode group
+pub struct SwaypedPad<'a> {
+    rings: HashMap<(u32, u32), Axis>,
+    strips: HashMap<(u32, u32), Axis>,
+    pressed_usec: HashMap<(u32, u32), u64>,
+    config: PadConfig,
+    cmd_desc: &'a CommandDesc,
+}
+
+impl<'a> SwaypedPad<'a> {
+    pub fn new(cmd_desc: &'a CommandDesc, config: &TomlConfig) -> Self {
+        SwaypedPad {
+            rings: HashMap::new(),
+            strips: HashMap::new(),
+            pressed_usec: HashMap::new(),
+            config: config.pad.clone(),
+            cmd_desc,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.rings.clear();
+        self.strips.clear();
+        self.pressed_usec.clear();
+    }
+
+    pub async fn handle_button_event(&mut self, event: &TabletPadButtonEvent) -> Result<()> {
+        let group = event.mode_group();
+        let number = event.button_number();
+        if group.button_is_toggle(number) {
+            trace!(?number, mode = event.mode(), "pad mode toggle");
+        }
+
+        let Some((cmd, duration_ms)) = self.button(
+            group.index(),
+            number,
+            event.button_state(),
+            event.time_usec(),
+        ) else {
+            return Ok(());
+        };
+
+        self.send(cmd, event.mode(), duration_ms).await
+    }
+
+    pub async fn handle_ring_event(&mut self, event: &TabletPadRingEvent) -> Result<()> {
+        let key = (event.mode_group().index(), event.number());
+        let axis = self.rings.entry(key).or_default();
+
+        // positions grow counterclockwise
+        let steps = axis.update(event.position(), self.config.ring_step, Some(360.0));
+        let cmd = if steps > 0 {
+            InputCommand::PadRingCcw(key.1)
+        } else {
+            InputCommand::PadRingCw(key.1)
+        };
+
+        self.send_steps(cmd, steps, event.mode())
+    }
+
+    pub async fn handle_strip_event(&mut self, event: &TabletPadStripEvent) -> Result<()> {
+        let key = (event.mode_group().index(), event.number());
+        let axis = self.strips.entry(key).or_default();
+
+        // positions grow from the top
+        let steps = axis.update(event.position(), self.config.strip_step, None);
+        let cmd = if steps > 0 {
+            InputCommand::PadStripDown(key.1)
+        } else {
+            InputCommand::PadStripUp(key.1)
+        };
+
+        self.send_steps(cmd, steps, event.mode())
+    }
+
+    fn button(
+        &mut self,
+        group: u32,
+        number: u32,
+        state: ButtonState,
+        usec: u64,
+    ) -> Option<(InputCommand, u64)> {
+        let (action, duration_ms) = match state {
+            ButtonState::Pressed => {
+                self.pressed_usec.insert((group, number), usec);
+                (ButtonAction::Press, 0)
+            }
+            ButtonState::Released => {
+                let pressed = self.pressed_usec.remove(&(group, number))?;
+                (ButtonAction::Release, usec.saturating_sub(pressed) / 1000)
+            }
+        };
+
+        Some((InputCommand::PadButton(number, action), duration_ms))
+    }
+
+    async fn send(&self, cmd: InputCommand, mode: u32, duration_ms: u64) -> Result<()> {
+        if !self.cmd_desc.mapped(&cmd) {
+            return Ok(());
+        }
+
+        debug!(?cmd, ?mode, ?duration_ms, "pad");
+        let ctx = CommandContext {
+            duration_ms,
+            pad_mode: Some(mode),
+            ..Default::default()
+        };
+        self.cmd_desc.send_with_context(cmd, ctx).await
+    }
+
+    /// Sends `cmd` once per step, a fast turn may cover more steps than the
+    /// channel holds, which only this task drains, the rest is dropped
+    fn send_steps(&self, cmd: InputCommand, steps: i64, mode: u32) -> Result<()> {
+        if steps == 0 || !self.cmd_desc.mapped(&cmd) {
+            return Ok(());
+        }
+
+        debug!(?cmd, ?mode, ?steps, "pad");
+        for _ in 0..steps.unsigned_abs() {
+            let ctx = CommandContext {
+                pad_mode: Some(mode),
+                ..Default::default()
+            };
+            if !self.cmd_desc.try_send_with_context(cmd.clone(), ctx)? {
+                warn!("Too many pad steps at once, dropping the rest");
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn test_swayped_pad_button() {
+        let (tx, _) = mpsc::channel(1);
+        let config = TomlConfig::default();
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut pad = SwaypedPad::new(&cmd_desc, &config);
+
+        assert_eq!(
+            pad.button(0, 2, ButtonState::Pressed, 1_000_000),
+            Some((InputCommand::PadButton(2, ButtonAction::Press), 0))
+        );
+        // same button number in another mode group
+        assert_eq!(pad.button(1, 2, ButtonState::Released, 1_100_000), None);
+        assert_eq!(
+            pad.button(0, 2, ButtonState::Released, 1_600_000),
+            Some((InputCommand::PadButton(2, ButtonAction::Release), 600))
+        );
+    }
+
+    #[test]
+    fn test_swayped_pad_ring() {
+        let mut axis = Axis::default();
+
+        assert_eq!(axis.update(350.0, 15.0, Some(360.0)), 0);
+        assert_eq!(axis.update(0.0, 15.0, Some(360.0)), 0);
+        // wraps around north
+        assert_eq!(axis.update(10.0, 15.0, Some(360.0)), 1);
+        assert_eq!(axis.update(0.0, 15.0, Some(360.0)), 0);
+        assert_eq!(axis.update(340.0, 15.0, Some(360.0)), -1);
+
+        // lifting the finger forgets the position
+        assert_eq!(axis.update(-1.0, 15.0, Some(360.0)), 0);
+        assert_eq!(axis.update(90.0, 15.0, Some(360.0)), 0);
+    }
+
+    #[test]
+    fn test_swayped_pad_strip() {
+        let mut axis = Axis::default();
+
+        assert_eq!(axis.update(0.5, 0.1, None), 0);
+        assert_eq!(axis.update(0.55, 0.1, None), 0);
+        assert_eq!(axis.update(0.62, 0.1, None), 1);
+        assert_eq!(axis.update(0.4, 0.1, None), -1);
+    }
+
+    #[test]
+    fn test_swayped_pad_steps() {
+        let mut axis = Axis::default();
+
+        // a quick turn covering two and a half steps
+        assert_eq!(axis.update(0.0, 15.0, Some(360.0)), 0);
+        assert_eq!(axis.update(37.5, 15.0, Some(360.0)), 2);
+        assert_eq!(axis.update(45.0, 15.0, Some(360.0)), 1);
+        assert_eq!(axis.update(0.0, 15.0, Some(360.0)), -3);
+
+        let mut axis = Axis::default();
+
+        assert_eq!(axis.update(0.1, 0.1, None), 0);
+        assert_eq!(axis.update(0.45, 0.1, None), 3);
+    }
+}
@@ -1,24 +1,515 @@
 use anyhow::Result;
-use input::event::pointer::Axis::Horizontal;
-use input::event::pointer::PointerScrollWheelEvent;
-use tracing::debug;
+use input::event::pointer::Axis::{Horizontal, Vertical};
+use std::collections::HashMap;
+use std::iter;
+
+use input::event::pointer::{
+    ButtonState, PointerButtonEvent, PointerEventTrait, PointerScrollEvent,
+    PointerScrollFingerEvent, PointerScrollWheelEvent,
+};
+use tracing::{debug, warn};
 
 use crate::commands::CommandDesc;
-use crate::commands::InputCommand;
-
-pub async fn pointer_handle_scroll_event(
-    event: &PointerScrollWheelEvent,
-    cmd_desc: &CommandDesc,
-) -> Result<()> {
-    let horiz = event.scroll_value_v120(Horizontal);
-
-    if horiz > 0.0 {
-        debug!("scroll right");
-        cmd_desc.send(InputCommand::ScrollRight).await?;
-    } else if horiz < 0.0 {
-        debug!("scroll left");
-        cmd_desc.send(InputCommand::ScrollLeft).await?;
-    };
-
-    Ok(())
+use crate::commands::{CommandContext, InputCommand};
+use crate::config::{ButtonAction, ScrollConfig, Source, TomlConfig};
+
+/// High resolution wheel units of a single notch
+const V120_PER_DETENT: f64 = 120.0;
+
+/// Linux input event code of the left mouse button, the others follow
+const BTN_LEFT: u32 = 0x110;
+const BUTTON_NAMES: [&str; 8] = [
+    "left", "right", "middle", "side", "extra", "forward", "back", "task",
+];
+
+/// Event code of a button given by name or by number, for the buttons past
+/// the named ones
+pub fn button_code(name: &str) -> Option<u32> {
+    BUTTON_NAMES
+        .iter()
+        .position(|x| *x == name)
+        .map(|i| BTN_LEFT + i as u32)
+        .or_else(|| name.parse().ok())
+}
+
+pub struct SwaypedPointer<'a> {
+    wheel_dx: f64,
+    wheel_dy: f64,
+    finger_dx: f64,
+    finger_dy: f64,
+    finger_usec: Option<u64>,
+    /// Axes of the scroll sequence libinput has not stopped yet
+    finger_axes: (bool, bool),
+    fired_usec: Option<u64>,
+    pressed_usec: HashMap<u32, u64>,
+    scroll: ScrollConfig,
+    cmd_desc: &'a CommandDesc,
+}
+
+impl<'a> SwaypedPointer<'a> {
+    pub fn new(cmd_desc: &'a CommandDesc, config: &TomlConfig) -> Self {
+        SwaypedPointer {
+            wheel_dx: 0.0,
+            wheel_dy: 0.0,
+            finger_dx: 0.0,
+            finger_dy: 0.0,
+            finger_usec: None,
+            finger_axes: (false, false),
+            fired_usec: None,
+            pressed_usec: HashMap::new(),
+            scroll: config.scroll.clone(),
+            cmd_desc,
+        }
+    }
+
+    /// Forget the buttons held and the scrolling in progress
+    pub fn reset(&mut self) {
+        self.wheel_dx = 0.0;
+        self.wheel_dy = 0.0;
+        self.finger_dx = 0.0;
+        self.finger_dy = 0.0;
+        self.finger_usec = None;
+        self.finger_axes = (false, false);
+        self.pressed_usec.clear();
+    }
+
+    pub async fn handle_scroll_event(&mut self, event: &PointerScrollWheelEvent) -> Result<()> {
+        let horiz = if event.has_axis(Horizontal) {
+            event.scroll_value_v120(Horizontal)
+        } else {
+            0.0
+        };
+        let vert = if event.has_axis(Vertical) {
+            event.scroll_value_v120(Vertical)
+        } else {
+            0.0
+        };
+
+        // a fast spin may cover more steps than the channel holds, which
+        // only this task drains, drop the rest instead of blocking on it
+        for cmd in self.wheel(horiz, vert, event.time_usec()) {
+            if !self
+                .cmd_desc
+                .try_send_with_context(cmd, CommandContext::default())?
+            {
+                warn!("Too many scroll steps at once, dropping the rest");
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn handle_button_event(&mut self, event: &PointerButtonEvent) -> Result<()> {
+        let Some((cmd, ctx)) = self.button(event.button(), event.button_state(), event.time_usec())
+        else {
+            return Ok(());
+        };
+
+        self.cmd_desc.send_with_context(cmd, ctx).await?;
+        Ok(())
+    }
+
+    /// Button mapping of a press or release, releases carry how long the
+    /// button was held
+    fn button(
+        &mut self,
+        code: u32,
+        state: ButtonState,
+        usec: u64,
+    ) -> Option<(InputCommand, CommandContext)> {
+        let (action, duration_ms) = match state {
+            ButtonState::Pressed => {
+                self.pressed_usec.insert(code, usec);
+                (ButtonAction::Press, 0)
+            }
+            ButtonState::Released => {
+                let pressed = self.pressed_usec.remove(&code)?;
+                (ButtonAction::Release, usec.saturating_sub(pressed) / 1000)
+            }
+        };
+
+        let cmd = InputCommand::Button(code, action);
+        if !self.cmd_desc.mapped(&cmd) {
+            return None;
+        }
+
+        debug!(?cmd, ?duration_ms, "button");
+        let ctx = CommandContext {
+            duration_ms,
+            ..Default::default()
+        };
+        Some((cmd, ctx))
+    }
+
+    pub async fn handle_scroll_finger_event(
+        &mut self,
+        event: &PointerScrollFingerEvent,
+    ) -> Result<()> {
+        let value = |axis| event.has_axis(axis).then(|| event.scroll_value(axis));
+
+        if let Some(cmd) = self.finger(value(Horizontal), value(Vertical), event.time_usec()) {
+            let ctx = CommandContext {
+                source: Some(Source::Touchpad),
+                ..Default::default()
+            };
+            self.cmd_desc.send_with_context(cmd, ctx).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Accumulate two finger scrolling until libinput stops every axis of the
+    /// scroll sequence with a zero value, then classify it along its main axis
+    fn finger(&mut self, horiz: Option<f64>, vert: Option<f64>, usec: u64) -> Option<InputCommand> {
+        let mapped = [
+            InputCommand::ScrollFingerLeft,
+            InputCommand::ScrollFingerRight,
+            InputCommand::ScrollFingerUp,
+            InputCommand::ScrollFingerDown,
+        ]
+        .iter()
+        .any(|x| self.cmd_desc.mapped(x));
+        if !mapped {
+            return None;
+        }
+
+        let begin_usec = *self.finger_usec.get_or_insert(usec);
+        self.finger_dx += horiz.unwrap_or(0.0);
+        self.finger_dy += vert.unwrap_or(0.0);
+
+        // each axis is stopped on its own, a diagonal scroll or one changing
+        // axis only ends once both are
+        if let Some(value) = horiz {
+            self.finger_axes.0 = value != 0.0;
+        }
+        if let Some(value) = vert {
+            self.finger_axes.1 = value != 0.0;
+        }
+        let stopped = horiz == Some(0.0) || vert == Some(0.0);
+        if !stopped || self.finger_axes.0 || self.finger_axes.1 {
+            return None;
+        }
+
+        let dx = std::mem::take(&mut self.finger_dx);
+        let dy = std::mem::take(&mut self.finger_dy);
+        self.finger_usec = None;
+        if self.cmd_desc.typing(begin_usec) {
+            debug!("typing, ignoring scroll");
+            return None;
+        }
+        let threshold = self.scroll.finger_threshold;
+
+        let cmd = if dx.abs() >= dy.abs() && dx.abs() >= threshold {
+            if dx > 0.0 {
+                InputCommand::ScrollFingerRight
+            } else {
+                InputCommand::ScrollFingerLeft
+            }
+        } else if dy.abs() > dx.abs() && dy.abs() >= threshold {
+            if dy > 0.0 {
+                InputCommand::ScrollFingerDown
+            } else {
+                InputCommand::ScrollFingerUp
+            }
+        } else {
+            return None;
+        };
+
+        debug!(?cmd, ?dx, ?dy, "scroll finger");
+        self.cmd_desc.mapped(&cmd).then_some(cmd)
+    }
+
+    /// Accumulate wheel rotation until a full step is reached on an axis
+    fn wheel(&mut self, horiz: f64, vert: f64, usec: u64) -> Vec<InputCommand> {
+        if self.cmd_desc.typing(usec) {
+            debug!("typing, ignoring scroll");
+            return vec![];
+        }
+
+        let cooldown = self.scroll.cooldown_ms * 1000;
+        if self
+            .fired_usec
+            .is_some_and(|fired| usec.saturating_sub(fired) < cooldown)
+        {
+            return vec![];
+        }
+
+        let step = self.scroll.detents * V120_PER_DETENT;
+        let mut cmds = vec![];
+
+        let (left, right) = (InputCommand::ScrollLeft, InputCommand::ScrollRight);
+        if self.cmd_desc.mapped(&left) || self.cmd_desc.mapped(&right) {
+            let steps = accumulate(&mut self.wheel_dx, horiz, step);
+            if steps != 0 {
+                debug!(?steps, "scroll horizontal");
+                let cmd = if steps > 0 { right } else { left };
+                cmds.extend(iter::repeat_n(cmd, steps.unsigned_abs() as usize));
+            }
+        }
+
+        let (up, down) = (InputCommand::ScrollUp, InputCommand::ScrollDown);
+        if self.cmd_desc.mapped(&up) || self.cmd_desc.mapped(&down) {
+            let steps = accumulate(&mut self.wheel_dy, vert, step);
+            if steps != 0 {
+                debug!(?steps, "scroll vertical");
+                let cmd = if steps > 0 { down } else { up };
+                cmds.extend(iter::repeat_n(cmd, steps.unsigned_abs() as usize));
+            }
+        }
+
+        if !cmds.is_empty() {
+            self.fired_usec = Some(usec);
+            if cooldown > 0 {
+                self.wheel_dx = 0.0;
+                self.wheel_dy = 0.0;
+            }
+        }
+
+        cmds
+    }
+}
+
+/// Add `value` to the running total of an axis, starting over when the
+/// direction changes, returns the whole steps reached, negative going left
+/// or up, and keeps the remainder for later
+fn accumulate(total: &mut f64, value: f64, step: f64) -> i64 {
+    if value == 0.0 {
+        return 0;
+    }
+
+    if *total * value < 0.0 {
+        *total = 0.0;
+    }
+    *total += value;
+
+    let steps = (*total / step).trunc();
+    *total -= steps * step;
+    steps as i64
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::config::Mapping;
+
+    fn config(scroll: ScrollConfig) -> TomlConfig {
+        let mapping = |gesture: &str| Mapping {
+            gesture: gesture.to_string(),
+            cmd: gesture.to_string(),
+            cmd_type: "sway".to_string(),
+            ..Default::default()
+        };
+
+        TomlConfig {
+            mappings: vec![
+                mapping("scrollwheel_left"),
+                mapping("scrollwheel_right"),
+                mapping("scrollwheel_up"),
+                mapping("scrollwheel_down"),
+                mapping("scrollfinger_left"),
+                mapping("scrollfinger_right"),
+            ],
+            scroll,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_swayped_pointer_wheel() {
+        let (tx, _) = mpsc::channel(1);
+        let config = config(ScrollConfig::default());
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut pointer = SwaypedPointer::new(&cmd_desc, &config);
+
+        // a high resolution wheel reports a notch in several events
+        assert!(pointer.wheel(40.0, 0.0, 0).is_empty());
+        assert!(pointer.wheel(40.0, 0.0, 1000).is_empty());
+        assert_eq!(pointer.wheel(40.0, 0.0, 2000), [InputCommand::ScrollRight]);
+
+        assert!(pointer.wheel(0.0, -60.0, 3000).is_empty());
+        assert_eq!(pointer.wheel(0.0, -60.0, 4000), [InputCommand::ScrollUp]);
+        assert_eq!(pointer.wheel(0.0, 120.0, 5000), [InputCommand::ScrollDown]);
+
+        // going back starts over
+        assert!(pointer.wheel(-60.0, 0.0, 6000).is_empty());
+        assert!(pointer.wheel(60.0, 0.0, 7000).is_empty());
+        assert_eq!(
+            pointer.wheel(-240.0, 0.0, 9000),
+            [InputCommand::ScrollLeft, InputCommand::ScrollLeft]
+        );
+        assert_eq!(pointer.wheel_dx, 0.0);
+    }
+
+    #[test]
+    fn test_swayped_pointer_wheel_steps() {
+        let (tx, _) = mpsc::channel(1);
+        let config = config(ScrollConfig::default());
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut pointer = SwaypedPointer::new(&cmd_desc, &config);
+
+        // one event covering two and a half notches
+        assert_eq!(
+            pointer.wheel(0.0, 300.0, 0),
+            [InputCommand::ScrollDown, InputCommand::ScrollDown]
+        );
+        assert_eq!(pointer.wheel_dy, 60.0);
+
+        // the half notch left needs the other half
+        assert!(pointer.wheel(0.0, 30.0, 1000).is_empty());
+        assert_eq!(pointer.wheel(0.0, 30.0, 2000), [InputCommand::ScrollDown]);
+        assert_eq!(
+            pointer.wheel(0.0, -250.0, 3000),
+            [InputCommand::ScrollUp, InputCommand::ScrollUp]
+        );
+    }
+
+    #[test]
+    fn test_swayped_pointer_wheel_cooldown() {
+        let (tx, _) = mpsc::channel(1);
+        let config = config(ScrollConfig {
+            detents: 2.0,
+            cooldown_ms: 300,
+            ..Default::default()
+        });
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut pointer = SwaypedPointer::new(&cmd_desc, &config);
+
+        assert!(pointer.wheel(120.0, 0.0, 0).is_empty());
+        assert_eq!(
+            pointer.wheel(120.0, 0.0, 10_000),
+            [InputCommand::ScrollRight]
+        );
+
+        // notches within the cooldown are dropped
+        assert!(pointer.wheel(240.0, 0.0, 100_000).is_empty());
+        assert!(pointer.wheel(120.0, 0.0, 310_000).is_empty());
+        assert_eq!(
+            pointer.wheel(120.0, 0.0, 320_000),
+            [InputCommand::ScrollRight]
+        );
+    }
+
+    #[test]
+    fn test_swayped_pointer_finger() {
+        let (tx, _) = mpsc::channel(1);
+        let config = config(ScrollConfig {
+            finger_threshold: 100.0,
+            ..Default::default()
+        });
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut pointer = SwaypedPointer::new(&cmd_desc, &config);
+
+        assert!(pointer.finger(Some(-30.0), Some(5.0), 0).is_none());
+        assert!(pointer.finger(Some(-50.0), Some(-3.0), 0).is_none());
+        assert!(pointer.finger(Some(-40.0), None, 0).is_none());
+        assert_eq!(
+            pointer.finger(Some(0.0), Some(0.0), 0),
+            Some(InputCommand::ScrollFingerLeft)
+        );
+
+        // a short scroll does not reach the threshold
+        assert!(pointer.finger(Some(60.0), None, 0).is_none());
+        assert!(pointer.finger(Some(0.0), None, 0).is_none());
+
+        // vertical scrolling is not mapped
+        assert!(pointer.finger(None, Some(300.0), 0).is_none());
+        assert!(pointer.finger(None, Some(0.0), 0).is_none());
+
+        // the vertical axis stops while the horizontal one keeps going
+        assert!(pointer.finger(Some(-40.0), Some(30.0), 0).is_none());
+        assert!(pointer.finger(Some(-40.0), Some(0.0), 0).is_none());
+        assert!(pointer.finger(Some(-40.0), None, 0).is_none());
+        assert!(pointer.finger(Some(-40.0), None, 0).is_none());
+        assert_eq!(
+            pointer.finger(Some(0.0), None, 0),
+            Some(InputCommand::ScrollFingerLeft)
+        );
+    }
+
+    #[test]
+    fn test_swayped_pointer_button() {
+        let (tx, _) = mpsc::channel(1);
+        let config = TomlConfig {
+            mappings: vec![
+                Mapping {
+                    gesture: "button".to_string(),
+                    button: Some("side".to_string()),
+                    cmd: "workspace prev".to_string(),
+                    cmd_type: "sway".to_string(),
+                    ..Default::default()
+                },
+                Mapping {
+                    gesture: "button".to_string(),
+                    button: Some("280".to_string()),
+                    action: Some(ButtonAction::Release),
+                    cmd: "workspace next".to_string(),
+                    cmd_type: "sway".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut pointer = SwaypedPointer::new(&cmd_desc, &config);
+
+        let (cmd, _) = pointer.button(0x113, ButtonState::Pressed, 0).unwrap();
+        assert_eq!(cmd, InputCommand::Button(0x113, ButtonAction::Press));
+        assert!(pointer.button(0x113, ButtonState::Released, 0).is_none());
+
+        assert!(pointer.button(280, ButtonState::Pressed, 100_000).is_none());
+        let (cmd, ctx) = pointer.button(280, ButtonState::Released, 700_000).unwrap();
+        assert_eq!(cmd, InputCommand::Button(280, ButtonAction::Release));
+        assert_eq!(ctx.duration_ms, 600);
+
+        // a release without its press, e.g. when starting with the button held
+        assert!(pointer
+            .button(280, ButtonState::Released, 800_000)
+            .is_none());
+
+        assert_eq!(button_code("left"), Some(0x110));
+        assert_eq!(button_code("extra"), Some(0x114));
+        assert_eq!(button_code("bogus"), None);
+    }
+
+    #[test]
+    fn test_swayped_pointer_typing() {
+        let (tx, _) = mpsc::channel(1);
+        let mut config = config(ScrollConfig::default());
+        config.recognition.typing_timeout_ms = Some(500);
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+        cmd_desc.set_last_key(1_000_000);
+
+        let mut pointer = SwaypedPointer::new(&cmd_desc, &config);
+
+        assert!(pointer.wheel(120.0, 0.0, 1_200_000).is_empty());
+        assert_eq!(
+            pointer.wheel(120.0, 0.0, 1_600_000),
+            [InputCommand::ScrollRight]
+        );
+
+        // the scroll sequence started while typing
+        assert!(pointer.finger(Some(-200.0), None, 1_400_000).is_none());
+        assert!(pointer.finger(Some(-200.0), None, 1_600_000).is_none());
+        assert!(pointer.finger(Some(0.0), None, 1_700_000).is_none());
+
+        assert!(pointer.finger(Some(-200.0), None, 1_800_000).is_none());
+        assert_eq!(
+            pointer.finger(Some(0.0), None, 1_900_000),
+            Some(InputCommand::ScrollFingerLeft)
+        );
+    }
 }
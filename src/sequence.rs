@@ -0,0 +1,290 @@
+use std::mem;
+
+use tokio::time::{Duration, Instant};
+use tracing::debug;
+
+use crate::commands::{CommandContext, CommandDesc, InputCommand};
+
+/// Recognized gestures held back because they start a sequence mapping
+#[derive(Debug, Default)]
+pub struct SwaypedSequence {
+    pending: Vec<(InputCommand, CommandContext)>,
+    start: Option<Instant>,
+    deadline: Option<Instant>,
+}
+
+impl SwaypedSequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Feed a recognized gesture, returns the commands ready to be processed
+    pub fn push(
+        &mut self,
+        cmd: InputCommand,
+        ctx: CommandContext,
+        cmd_desc: &CommandDesc,
+    ) -> Vec<(InputCommand, CommandContext)> {
+        self.push_at(cmd, ctx, cmd_desc, Instant::now())
+    }
+
+    /// Give up waiting for the rest of the pending sequence
+    pub fn timeout(&mut self, cmd_desc: &CommandDesc) -> Vec<(InputCommand, CommandContext)> {
+        debug!(pending = ?self.pending.len(), "sequence timeout");
+        self.flush(cmd_desc)
+    }
+
+    fn push_at(
+        &mut self,
+        cmd: InputCommand,
+        ctx: CommandContext,
+        cmd_desc: &CommandDesc,
+        now: Instant,
+    ) -> Vec<(InputCommand, CommandContext)> {
+        let mut ready = vec![];
+
+        if self.deadline.is_some_and(|deadline| now >= deadline) {
+            ready.extend(self.timeout(cmd_desc));
+        }
+
+        let mut cmds: Vec<InputCommand> = self.pending.iter().map(|(x, _)| x.clone()).collect();
+        cmds.push(cmd.clone());
+
+        // wait for the next gesture as long as a longer sequence may match
+        if let Some(timeout) = cmd_desc.sequence_timeout(&cmds) {
+            let start = *self.start.get_or_insert(now);
+            self.deadline = Some(start + Duration::from_millis(timeout));
+            self.pending.push((cmd, ctx));
+            debug!(?cmds, "sequence pending");
+            return ready;
+        }
+
+        let sequence = InputCommand::Sequence(cmds);
+        if !self.pending.is_empty() && cmd_desc.contains(&sequence) {
+            self.clear();
+            ready.push((sequence, ctx));
+            return ready;
+        }
+
+        // the gesture breaks the pending sequence, it may start another one
+        if self.pending.is_empty() {
+            ready.push((cmd, ctx));
+        } else {
+            ready.extend(self.flush(cmd_desc));
+            ready.extend(self.push_at(cmd, ctx, cmd_desc, now));
+        }
+
+        ready
+    }
+
+    fn flush(&mut self, cmd_desc: &CommandDesc) -> Vec<(InputCommand, CommandContext)> {
+        let pending = mem::take(&mut self.pending);
+        self.clear();
+
+        if pending.len() > 1 {
+            let cmds = pending.iter().map(|(x, _)| x.clone()).collect();
+            let sequence = InputCommand::Sequence(cmds);
+            if cmd_desc.contains(&sequence) {
+                let ctx = pending
+                    .last()
+                    .map(|(_, ctx)| ctx.clone())
+                    .unwrap_or_default();
+                return vec![(sequence, ctx)];
+            }
+        }
+
+        pending
+    }
+
+    fn clear(&mut self) {
+        self.pending.clear();
+        self.start = None;
+        self.deadline = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::config::{Mapping, SequenceStep, TomlConfig};
+
+    fn step(gesture: &str) -> SequenceStep {
+        SequenceStep {
+            gesture: gesture.to_string(),
+            finger_count: Some(3),
+        }
+    }
+
+    fn config() -> TomlConfig {
+        TomlConfig {
+            mappings: vec![
+                Mapping {
+                    gesture: "sequence".to_string(),
+                    sequence: Some(vec![step("swipe_up"), step("swipe_left")]),
+                    timeout_ms: Some(600),
+                    cmd: "up left".to_string(),
+                    cmd_type: "sway".to_string(),
+                    ..Default::default()
+                },
+                Mapping {
+                    gesture: "sequence".to_string(),
+                    sequence: Some(vec![
+                        step("swipe_up"),
+                        step("swipe_left"),
+                        step("swipe_down"),
+                    ]),
+                    cmd: "up left down".to_string(),
+                    cmd_type: "sway".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_swayped_sequence_single() {
+        let (tx, _) = mpsc::channel(1);
+        let config = config();
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut sequence = SwaypedSequence::new();
+        let now = Instant::now();
+
+        let ready = sequence.push_at(
+            InputCommand::SwipeDown(3),
+            CommandContext::default(),
+            &cmd_desc,
+            now,
+        );
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].0, InputCommand::SwipeDown(3));
+        assert!(sequence.deadline().is_none());
+    }
+
+    #[test]
+    fn test_swayped_sequence_match() {
+        let (tx, _) = mpsc::channel(1);
+        let config = config();
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut sequence = SwaypedSequence::new();
+        let now = Instant::now();
+
+        let ready = sequence.push_at(
+            InputCommand::SwipeUp(3),
+            CommandContext::default(),
+            &cmd_desc,
+            now,
+        );
+        assert!(ready.is_empty());
+        assert_eq!(sequence.deadline(), Some(now + Duration::from_millis(600)));
+
+        // a longer sequence starts the same way, keep waiting
+        let ready = sequence.push_at(
+            InputCommand::SwipeLeft(3),
+            CommandContext::default(),
+            &cmd_desc,
+            now + Duration::from_millis(200),
+        );
+        assert!(ready.is_empty());
+
+        let ready = sequence.push_at(
+            InputCommand::SwipeDown(3),
+            CommandContext::default(),
+            &cmd_desc,
+            now + Duration::from_millis(400),
+        );
+        assert_eq!(ready.len(), 1);
+        assert_eq!(
+            ready[0].0,
+            InputCommand::Sequence(vec![
+                InputCommand::SwipeUp(3),
+                InputCommand::SwipeLeft(3),
+                InputCommand::SwipeDown(3),
+            ])
+        );
+        assert!(sequence.deadline().is_none());
+    }
+
+    #[test]
+    fn test_swayped_sequence_timeout() {
+        let (tx, _) = mpsc::channel(1);
+        let config = config();
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut sequence = SwaypedSequence::new();
+        let now = Instant::now();
+
+        sequence.push_at(
+            InputCommand::SwipeUp(3),
+            CommandContext::default(),
+            &cmd_desc,
+            now,
+        );
+        sequence.push_at(
+            InputCommand::SwipeLeft(3),
+            CommandContext::default(),
+            &cmd_desc,
+            now,
+        );
+
+        let ready = sequence.timeout(&cmd_desc);
+        assert_eq!(ready.len(), 1);
+        assert_eq!(
+            ready[0].0,
+            InputCommand::Sequence(vec![InputCommand::SwipeUp(3), InputCommand::SwipeLeft(3)])
+        );
+
+        // falls back to the single gesture once too late for a sequence
+        sequence.push_at(
+            InputCommand::SwipeUp(3),
+            CommandContext::default(),
+            &cmd_desc,
+            now,
+        );
+        let ready = sequence.push_at(
+            InputCommand::SwipeLeft(3),
+            CommandContext::default(),
+            &cmd_desc,
+            now + Duration::from_millis(700),
+        );
+        assert_eq!(ready.len(), 2);
+        assert_eq!(ready[0].0, InputCommand::SwipeUp(3));
+        assert_eq!(ready[1].0, InputCommand::SwipeLeft(3));
+    }
+
+    #[test]
+    fn test_swayped_sequence_broken() {
+        let (tx, _) = mpsc::channel(1);
+        let config = config();
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut sequence = SwaypedSequence::new();
+        let now = Instant::now();
+
+        sequence.push_at(
+            InputCommand::SwipeUp(3),
+            CommandContext::default(),
+            &cmd_desc,
+            now,
+        );
+
+        // the unrelated gesture flushes the pending one, then starts over
+        let ready = sequence.push_at(
+            InputCommand::SwipeRight(3),
+            CommandContext::default(),
+            &cmd_desc,
+            now,
+        );
+        assert_eq!(ready.len(), 2);
+        assert_eq!(ready[0].0, InputCommand::SwipeUp(3));
+        assert_eq!(ready[1].0, InputCommand::SwipeRight(3));
+    }
+}
@@ -0,0 +1,256 @@
+use std::f64::consts::PI;
+
+use crate::config::ShapesConfig;
+
+pub type Point = (f64, f64);
+
+const RESAMPLE_POINTS: usize = 64;
+const TEMPLATE_POINTS: usize = 16;
+const SQUARE_SIZE: f64 = 250.0;
+const ANGLE_RANGE: f64 = PI / 4.0;
+const ANGLE_PRECISION: f64 = PI / 90.0;
+
+/// Template matching of drawn paths, following the $1 unistroke recognizer
+/// with a uniform scaling so that straight strokes are supported as well
+#[derive(Debug)]
+pub struct ShapeRecognizer {
+    templates: Vec<(String, Vec<Point>)>,
+    min_score: f64,
+}
+
+impl ShapeRecognizer {
+    pub fn new(config: &ShapesConfig) -> Self {
+        let templates = config
+            .templates
+            .iter()
+            .filter(|x| path_length(&x.points) > 0.0)
+            .map(|x| (x.name.clone(), normalize(&x.points)))
+            .collect();
+
+        ShapeRecognizer {
+            templates,
+            min_score: config.min_score,
+        }
+    }
+
+    /// Best matching template for `path` along with its score between 0 and 1
+    pub fn recognize(&self, path: &[Point]) -> Option<(&str, f64)> {
+        if path.len() < 2 || path_length(path) <= 0.0 {
+            return None;
+        }
+
+        let points = normalize(path);
+        let half_diagonal = 0.5 * (2.0 * SQUARE_SIZE * SQUARE_SIZE).sqrt();
+
+        self.templates
+            .iter()
+            .map(|(name, template)| {
+                let distance = distance_at_best_angle(&points, template);
+                (name.as_str(), 1.0 - distance / half_diagonal)
+            })
+            .filter(|(_, score)| *score >= self.min_score)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+/// Resample, scale and center a path so that it can be compared to others
+pub fn normalize(path: &[Point]) -> Vec<Point> {
+    let points = resample(path, RESAMPLE_POINTS);
+    let points = scale_to_square(&points, SQUARE_SIZE);
+    translate_to_origin(&points)
+}
+
+/// Config entry of a recorded path, scaled to fit in a 100 units square
+pub fn template(name: &str, path: &[Point]) -> String {
+    let points = resample(path, TEMPLATE_POINTS);
+    let points = scale_to_square(&points, 100.0);
+    let min_x = points.iter().map(|p| p.0).fold(f64::INFINITY, f64::min);
+    let min_y = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+
+    let points = points
+        .iter()
+        .map(|p| format!("[{:.0}, {:.0}]", p.0 - min_x, p.1 - min_y))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!("{{ name = {:?}, points = [{}] }}", name, points)
+}
+
+pub fn path_length(points: &[Point]) -> f64 {
+    points
+        .windows(2)
+        .map(|x| (x[1].0 - x[0].0).hypot(x[1].1 - x[0].1))
+        .sum()
+}
+
+/// Spread `n` points evenly along the path
+pub fn resample(path: &[Point], n: usize) -> Vec<Point> {
+    let interval = path_length(path) / (n - 1) as f64;
+    let mut points = path.to_vec();
+    let mut resampled = vec![points[0]];
+    let mut distance = 0.0;
+
+    let mut i = 1;
+    while i < points.len() {
+        let (prev, cur) = (points[i - 1], points[i]);
+        let d = (cur.0 - prev.0).hypot(cur.1 - prev.1);
+        if distance + d >= interval && d > 0.0 {
+            let t = (interval - distance) / d;
+            let point = (prev.0 + t * (cur.0 - prev.0), prev.1 + t * (cur.1 - prev.1));
+            resampled.push(point);
+            points.insert(i, point);
+            distance = 0.0;
+        } else {
+            distance += d;
+        }
+        i += 1;
+    }
+
+    // rounding errors may leave the last point out
+    while resampled.len() < n {
+        resampled.push(*path.last().unwrap());
+    }
+    resampled.truncate(n);
+
+    resampled
+}
+
+fn centroid(points: &[Point]) -> Point {
+    let n = points.len() as f64;
+    let (x, y) = points
+        .iter()
+        .fold((0.0, 0.0), |acc, p| (acc.0 + p.0, acc.1 + p.1));
+    (x / n, y / n)
+}
+
+fn scale_to_square(points: &[Point], size: f64) -> Vec<Point> {
+    let min_x = points.iter().map(|p| p.0).fold(f64::INFINITY, f64::min);
+    let max_x = points.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max);
+    let min_y = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+    let max_y = points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+    let scale = size / (max_x - min_x).max(max_y - min_y);
+
+    points.iter().map(|p| (p.0 * scale, p.1 * scale)).collect()
+}
+
+fn translate_to_origin(points: &[Point]) -> Vec<Point> {
+    let (cx, cy) = centroid(points);
+    points.iter().map(|p| (p.0 - cx, p.1 - cy)).collect()
+}
+
+fn rotate_by(points: &[Point], angle: f64) -> Vec<Point> {
+    let (cx, cy) = centroid(points);
+    let (sin, cos) = angle.sin_cos();
+    points
+        .iter()
+        .map(|p| {
+            let (x, y) = (p.0 - cx, p.1 - cy);
+            (x * cos - y * sin + cx, x * sin + y * cos + cy)
+        })
+        .collect()
+}
+
+fn path_distance(a: &[Point], b: &[Point]) -> f64 {
+    let d: f64 = a
+        .iter()
+        .zip(b)
+        .map(|(p, q)| (q.0 - p.0).hypot(q.1 - p.1))
+        .sum();
+    d / a.len() as f64
+}
+
+/// Golden section search of the rotation best aligning the two paths
+fn distance_at_best_angle(points: &[Point], template: &[Point]) -> f64 {
+    let phi = 0.5 * (5.0_f64.sqrt() - 1.0);
+    let (mut a, mut b) = (-ANGLE_RANGE, ANGLE_RANGE);
+    let mut x1 = phi * a + (1.0 - phi) * b;
+    let mut f1 = path_distance(&rotate_by(points, x1), template);
+    let mut x2 = (1.0 - phi) * a + phi * b;
+    let mut f2 = path_distance(&rotate_by(points, x2), template);
+
+    while (b - a).abs() > ANGLE_PRECISION {
+        if f1 < f2 {
+            b = x2;
+            x2 = x1;
+            f2 = f1;
+            x1 = phi * a + (1.0 - phi) * b;
+            f1 = path_distance(&rotate_by(points, x1), template);
+        } else {
+            a = x1;
+            x1 = x2;
+            f1 = f2;
+            x2 = (1.0 - phi) * a + phi * b;
+            f2 = path_distance(&rotate_by(points, x2), template);
+        }
+    }
+
+    f1.min(f2)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::ShapeTemplate;
+
+    fn config() -> ShapesConfig {
+        ShapesConfig {
+            min_score: 0.8,
+            templates: vec![
+                ShapeTemplate {
+                    name: "L".to_string(),
+                    points: vec![(0.0, 0.0), (0.0, 100.0), (60.0, 100.0)],
+                },
+                ShapeTemplate {
+                    name: "Z".to_string(),
+                    points: vec![(0.0, 0.0), (100.0, 0.0), (0.0, 100.0), (100.0, 100.0)],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_shape_resample() {
+        let points = resample(&[(0.0, 0.0), (0.0, 100.0), (50.0, 100.0)], 4);
+        assert_eq!(points.len(), 4);
+        assert_eq!(points[0], (0.0, 0.0));
+        assert!((points[1].1 - 50.0).abs() < 1e-9);
+        assert!((points[3].0 - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_shape_recognize() {
+        let recognizer = ShapeRecognizer::new(&config());
+
+        // a sloppy, larger L drawn with a few intermediate points
+        let path = vec![
+            (0.0, 0.0),
+            (4.0, 120.0),
+            (2.0, 250.0),
+            (80.0, 255.0),
+            (150.0, 248.0),
+        ];
+        let (name, score) = recognizer.recognize(&path).unwrap();
+        assert_eq!(name, "L");
+        assert!(score >= 0.8);
+
+        let path = vec![(0.0, 0.0), (200.0, 10.0), (10.0, 190.0), (210.0, 200.0)];
+        let (name, _) = recognizer.recognize(&path).unwrap();
+        assert_eq!(name, "Z");
+
+        // a straight swipe is neither
+        let path = vec![(0.0, 0.0), (300.0, 0.0)];
+        assert!(recognizer.recognize(&path).is_none());
+    }
+
+    #[test]
+    fn test_shape_template() {
+        let path = vec![(10.0, 10.0), (10.0, 210.0), (110.0, 210.0)];
+        let template = template("L", &path);
+        assert!(template.starts_with(r#"{ name = "L", points = [[0, 0], "#));
+        assert!(template.ends_with("[50, 100]] }"));
+
+        // the printed template parses back into the config
+        let config: ShapesConfig = toml::from_str(&format!("templates = [{}]", template)).unwrap();
+        assert_eq!(config.templates[0].points.len(), TEMPLATE_POINTS);
+    }
+}
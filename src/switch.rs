@@ -0,0 +1,115 @@
+use anyhow::Result;
+use input::event::switch::{Switch, SwitchState, SwitchToggleEvent};
+use tracing::{debug, warn};
+
+use crate::commands::{CommandDesc, InputCommand};
+use crate::config::SwitchCondition;
+
+/// Last known state of the lid and tablet mode switches, both off until
+/// libinput reports otherwise
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Switches {
+    pub lid_closed: bool,
+    pub tablet_mode: bool,
+}
+
+impl Switches {
+    pub fn satisfies(&self, condition: SwitchCondition) -> bool {
+        match condition {
+            SwitchCondition::LidOpen => !self.lid_closed,
+            SwitchCondition::LidClose => self.lid_closed,
+            SwitchCondition::TabletModeOn => self.tablet_mode,
+            SwitchCondition::TabletModeOff => !self.tablet_mode,
+        }
+    }
+}
+
+/// Maps the lid and tablet mode switch toggles to commands
+pub struct SwaypedSwitch<'a> {
+    switches: Switches,
+    cmd_desc: &'a CommandDesc,
+}
+
+impl<'a> SwaypedSwitch<'a> {
+    pub fn new(cmd_desc: &'a CommandDesc) -> Self {
+        SwaypedSwitch {
+            switches: Switches::default(),
+            cmd_desc,
+        }
+    }
+
+    pub async fn handle_toggle_event(&mut self, event: &SwitchToggleEvent) -> Result<()> {
+        let Some(switch) = event.switch() else {
+            warn!("Unknown switch toggled");
+            return Ok(());
+        };
+
+        self.toggle(switch, event.switch_state() == SwitchState::On)
+            .await
+    }
+
+    async fn toggle(&mut self, switch: Switch, on: bool) -> Result<()> {
+        let cmd = match (switch, on) {
+            (Switch::Lid, true) => InputCommand::LidClose,
+            (Switch::Lid, false) => InputCommand::LidOpen,
+            (Switch::TabletMode, true) => InputCommand::TabletModeOn,
+            (Switch::TabletMode, false) => InputCommand::TabletModeOff,
+            (switch, _) => {
+                warn!(?switch, "Unsupported switch");
+                return Ok(());
+            }
+        };
+
+        match switch {
+            Switch::Lid => self.switches.lid_closed = on,
+            _ => self.switches.tablet_mode = on,
+        }
+        debug!(?cmd, switches = ?self.switches, "switch_toggle");
+        self.cmd_desc.set_switches(self.switches);
+
+        if self.cmd_desc.mapped(&cmd) {
+            self.cmd_desc.send(cmd).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::config::{Mapping, TomlConfig};
+
+    #[tokio::test]
+    async fn test_swayped_switch_toggle() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig {
+            mappings: vec![Mapping {
+                gesture: "tablet_mode_on".to_string(),
+                cmd: "input type:keyboard events disabled".to_string(),
+                cmd_type: "sway".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut switch = SwaypedSwitch::new(&cmd_desc);
+
+        switch.toggle(Switch::TabletMode, true).await.unwrap();
+        let (cmd, ctx) = rx.try_recv().unwrap();
+        assert_eq!(cmd, InputCommand::TabletModeOn);
+        assert!(ctx.switches.satisfies(SwitchCondition::TabletModeOn));
+        assert!(ctx.switches.satisfies(SwitchCondition::LidOpen));
+
+        // not mapped, only the state changes
+        switch.toggle(Switch::Lid, true).await.unwrap();
+        assert!(rx.try_recv().is_err());
+
+        cmd_desc.send(InputCommand::SwipeLeft(3)).await.unwrap();
+        let (_, ctx) = rx.try_recv().unwrap();
+        assert!(ctx.switches.satisfies(SwitchCondition::LidClose));
+    }
+}
@@ -0,0 +1,669 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::thread;
+
+use anyhow::{bail, Result};
+use input::event::touch::{TouchEvent, TouchEventPosition, TouchEventSlot, TouchEventTrait};
+use input::event::EventTrait;
+use swayipc::{Connection, Event as SwayEvent, EventType};
+use tokio::sync::watch;
+use tracing::{debug, trace, warn};
+
+use crate::commands::{CommandContext, CommandDesc, InputCommand};
+use crate::config::{PinchConfig, Source, SwipeConfig, Thresholds, TomlConfig, TouchscreenConfig};
+use crate::gesture::{pinch_command, swipe_command};
+use crate::shape::Point;
+
+/// Multi-finger and edge gestures recognized from the touch points of a
+/// touchscreen, positions are in millimeters from the top left corner of the
+/// screen as currently rotated
+pub struct SwaypedTouch<'a> {
+    active: bool,
+    slots: HashMap<u32, SwaypedTouchSlot>,
+    output: Option<SwaypedTouchOutput>,
+    size: Option<Point>,
+    screen: Option<Point>,
+    edge: Option<InputCommand>,
+    finger_count: i32,
+    begin_usec: u64,
+    time_usec: u64,
+    cancelled: bool,
+    touchscreen: TouchscreenConfig,
+    pinch: PinchConfig,
+    swipe: SwipeConfig,
+    thresholds: Thresholds,
+    cmd_desc: &'a CommandDesc,
+}
+
+#[derive(Debug)]
+struct SwaypedTouchSlot {
+    start: Point,
+    pos: Point,
+    down: bool,
+}
+
+/// Resolution and rotation of the output the touchscreen is mapped to
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwaypedTouchOutput {
+    width: f64,
+    height: f64,
+    transform: String,
+    scale: f64,
+}
+
+impl SwaypedTouchOutput {
+    /// Without the physical size of the touchscreen, assume 96 dpi at the
+    /// output scale
+    fn px_per_mm(&self, size: Option<Point>) -> f64 {
+        match size {
+            Some((w, _)) if w > 0.0 => self.width / w,
+            _ => 96.0 / 25.4 * self.scale,
+        }
+    }
+}
+
+impl<'a> SwaypedTouch<'a> {
+    pub fn new(cmd_desc: &'a CommandDesc, config: &TomlConfig) -> Self {
+        SwaypedTouch {
+            active: cmd_desc.has_touchscreen(),
+            slots: HashMap::new(),
+            output: None,
+            size: None,
+            screen: None,
+            edge: None,
+            finger_count: 0,
+            begin_usec: 0,
+            time_usec: 0,
+            cancelled: false,
+            touchscreen: config.touchscreen.clone(),
+            pinch: config.pinch.clone(),
+            swipe: config.swipe.clone(),
+            thresholds: Thresholds {
+                threshold: config.touchscreen.threshold_mm,
+                axis_ratio: config.recognition.axis_ratio,
+            },
+            cmd_desc,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.slots.clear();
+        self.edge = None;
+        self.finger_count = 0;
+        self.begin_usec = 0;
+        self.time_usec = 0;
+        self.cancelled = false;
+    }
+
+    fn down(&mut self, slot: u32, pos: Point, usec: u64) {
+        trace!(?slot, ?pos, "touch down");
+        if !self.slots.values().any(|x| x.down) {
+            self.reset();
+            self.begin_usec = usec;
+            self.edge = self.edge(pos);
+        }
+
+        self.slots.insert(
+            slot,
+            SwaypedTouchSlot {
+                start: pos,
+                pos,
+                down: true,
+            },
+        );
+        let fingers = self.slots.values().filter(|x| x.down).count() as i32;
+        self.finger_count = self.finger_count.max(fingers);
+        self.time_usec = usec;
+    }
+
+    fn motion(&mut self, slot: u32, pos: Point, usec: u64) {
+        if let Some(x) = self.slots.get_mut(&slot) {
+            x.pos = pos;
+        }
+        self.time_usec = usec;
+    }
+
+    async fn up(&mut self, slot: u32, usec: u64) -> Result<()> {
+        trace!(?slot, "touch up");
+        if let Some(x) = self.slots.get_mut(&slot) {
+            x.down = false;
+        }
+        self.time_usec = usec;
+
+        if !self.slots.is_empty() && !self.slots.values().any(|x| x.down) {
+            self.terminate().await?;
+            self.reset();
+        }
+        Ok(())
+    }
+
+    async fn cancel(&mut self, slot: u32, usec: u64) -> Result<()> {
+        trace!(?slot, "touch cancel");
+        self.cancelled = true;
+        self.up(slot, usec).await
+    }
+
+    pub async fn handle_event(&mut self, event: &TouchEvent) -> Result<()> {
+        if !self.active {
+            return Ok(());
+        }
+
+        match event {
+            TouchEvent::Down(event) => {
+                let size = event.device().size();
+                if size != self.size {
+                    self.size = size;
+                    self.update_screen();
+                }
+                let pos = self.position(event);
+                self.down(event.seat_slot(), pos, event.time_usec())
+            }
+            TouchEvent::Motion(event) => {
+                let pos = self.position(event);
+                self.motion(event.seat_slot(), pos, event.time_usec())
+            }
+            TouchEvent::Up(event) => self.up(event.seat_slot(), event.time_usec()).await?,
+            TouchEvent::Cancel(event) => self.cancel(event.seat_slot(), event.time_usec()).await?,
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Follow the output resolution and rotation, which change when a
+    /// convertible is rotated, as reported by [`watch_output`]
+    pub fn set_output(&mut self, output: Option<SwaypedTouchOutput>) {
+        self.output = output;
+        self.update_screen();
+    }
+
+    /// Size of the screen as rotated, falling back to the touchscreen size
+    fn update_screen(&mut self) {
+        self.screen = match &self.output {
+            Some(output) => {
+                let px_per_mm = output.px_per_mm(self.size);
+                let (width, height) =
+                    transform_size((output.width, output.height), &output.transform);
+                Some((width / px_per_mm, height / px_per_mm))
+            }
+            None => self.size,
+        };
+        trace!(?self.output, ?self.screen, "touchscreen output");
+    }
+
+    fn position<E: TouchEventPosition>(&self, event: &E) -> Point {
+        let Some(output) = &self.output else {
+            return (event.x(), event.y());
+        };
+
+        let pos = (
+            event.x_transformed(output.width as u32),
+            event.y_transformed(output.height as u32),
+        );
+        let (x, y) = transform_point(pos, (output.width, output.height), &output.transform);
+        let px_per_mm = output.px_per_mm(self.size);
+        (x / px_per_mm, y / px_per_mm)
+    }
+
+    /// Screen edge a touch starting at `pos` comes from
+    fn edge(&self, (x, y): Point) -> Option<InputCommand> {
+        let (width, height) = self.screen?;
+        let edge = self.touchscreen.edge_mm;
+
+        if x <= edge {
+            Some(InputCommand::EdgeLeft)
+        } else if x >= width - edge {
+            Some(InputCommand::EdgeRight)
+        } else if y <= edge {
+            Some(InputCommand::EdgeTop)
+        } else if y >= height - edge {
+            Some(InputCommand::EdgeBottom)
+        } else {
+            None
+        }
+    }
+
+    /// Single finger swipe moving inward from the edge it started on
+    fn edge_command(&self) -> Option<InputCommand> {
+        let (dx, dy) = self.delta();
+        let swipe = swipe_command(dx, dy, 1, &self.swipe, &self.thresholds)?;
+
+        match (self.edge.clone()?, swipe) {
+            (cmd @ InputCommand::EdgeLeft, InputCommand::SwipeRight(_))
+            | (cmd @ InputCommand::EdgeRight, InputCommand::SwipeLeft(_))
+            | (cmd @ InputCommand::EdgeTop, InputCommand::SwipeDown(_))
+            | (cmd @ InputCommand::EdgeBottom, InputCommand::SwipeUp(_)) => Some(cmd),
+            _ => None,
+        }
+    }
+
+    /// Average motion of the fingers
+    fn delta(&self) -> Point {
+        let n = self.slots.len() as f64;
+        let (dx, dy) = self.slots.values().fold((0.0, 0.0), |acc, x| {
+            (acc.0 + x.pos.0 - x.start.0, acc.1 + x.pos.1 - x.start.1)
+        });
+        (dx / n, dy / n)
+    }
+
+    /// Change of the distance between the fingers and of their angle around
+    /// their center, in degrees and positive clockwise
+    fn scale_angle(&self) -> (f64, f64) {
+        let n = self.slots.len() as f64;
+        let center = |f: fn(&SwaypedTouchSlot) -> Point| {
+            let (x, y) = self
+                .slots
+                .values()
+                .map(f)
+                .fold((0.0, 0.0), |acc, p| (acc.0 + p.0, acc.1 + p.1));
+            (x / n, y / n)
+        };
+        let start = center(|x| x.start);
+        let end = center(|x| x.pos);
+
+        let mut spread = (0.0, 0.0);
+        let mut angle = 0.0;
+        for x in self.slots.values() {
+            let (sx, sy) = (x.start.0 - start.0, x.start.1 - start.1);
+            let (ex, ey) = (x.pos.0 - end.0, x.pos.1 - end.1);
+            spread.0 += sx.hypot(sy);
+            spread.1 += ex.hypot(ey);
+
+            let mut delta = ey.atan2(ex) - sy.atan2(sx);
+            if delta > PI {
+                delta -= 2.0 * PI;
+            } else if delta < -PI {
+                delta += 2.0 * PI;
+            }
+            angle += delta;
+        }
+
+        let scale = if spread.0 > 0.0 {
+            spread.1 / spread.0
+        } else {
+            1.0
+        };
+        (scale, (angle / n).to_degrees())
+    }
+
+    /// Farthest distance a finger moved from where it touched the screen
+    fn max_distance(&self) -> f64 {
+        self.slots
+            .values()
+            .map(|x| (x.pos.0 - x.start.0).hypot(x.pos.1 - x.start.1))
+            .fold(0.0, f64::max)
+    }
+
+    async fn terminate(&mut self) -> Result<()> {
+        let finger_count = self.finger_count;
+        let duration_ms = self.time_usec.saturating_sub(self.begin_usec) / 1000;
+
+        if self.cancelled {
+            return Ok(());
+        }
+
+        let (dx, dy) = self.delta();
+        let (scale, angle) = self.scale_angle();
+        let still = self.max_distance() <= self.touchscreen.hold_tolerance_mm;
+
+        // single touches are left to the applications to tap and scroll,
+        // unless they come from an edge of the screen
+        let cmd = if finger_count < 2 {
+            self.edge_command()
+        } else if still {
+            (duration_ms >= self.touchscreen.hold_ms).then_some(InputCommand::Hold(finger_count))
+        } else {
+            pinch_command(scale, angle, finger_count, &self.pinch)
+                .or_else(|| swipe_command(dx, dy, finger_count, &self.swipe, &self.thresholds))
+        };
+
+        debug!(
+            ?cmd,
+            ?dx,
+            ?dy,
+            ?scale,
+            ?angle,
+            ?duration_ms,
+            ?finger_count,
+            "touch_process"
+        );
+
+        let Some(cmd) = cmd else {
+            return Ok(());
+        };
+
+        let ctx = CommandContext {
+            duration_ms,
+            scale,
+            angle,
+            source: Some(Source::Touchscreen),
+            ..Default::default()
+        };
+        self.cmd_desc.send_with_context(cmd, ctx).await?;
+
+        Ok(())
+    }
+}
+
+/// Geometry of the touchscreen output, sent again whenever sway reports an
+/// output change such as a rotation. Asking sway blocks, so it is followed
+/// from a thread of its own, and only when a touchscreen mapping needs it
+pub fn watch_output(
+    cmd_desc: &CommandDesc,
+    config: &TomlConfig,
+) -> watch::Receiver<Option<SwaypedTouchOutput>> {
+    let (tx, rx) = watch::channel(None);
+    if !cmd_desc.has_touchscreen() {
+        return rx;
+    }
+
+    let name = config.touchscreen.output.clone();
+    thread::spawn(move || {
+        if let Err(err) = follow_output(name.as_deref(), &tx) {
+            warn!(
+                ?err,
+                "Touchscreen output not followed, using the touchscreen size"
+            );
+        }
+    });
+    rx
+}
+
+fn follow_output(name: Option<&str>, tx: &watch::Sender<Option<SwaypedTouchOutput>>) -> Result<()> {
+    let mut conn = Connection::new()?;
+    let events = Connection::new()?.subscribe([EventType::Output])?;
+
+    let mut update = || {
+        let output = sway_output(&mut conn, name)
+            .inspect_err(|err| debug!(?err, "touchscreen output"))
+            .ok();
+        tx.send(output).is_ok()
+    };
+
+    if !update() {
+        return Ok(());
+    }
+    for event in events {
+        if let SwayEvent::Output(_) = event? {
+            if !update() {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Output the touchscreen is mapped to, by name or else the built-in panel
+fn sway_output(conn: &mut Connection, name: Option<&str>) -> Result<SwaypedTouchOutput> {
+    let outputs = conn.get_outputs()?;
+    let output = match name {
+        Some(name) => outputs.iter().find(|x| x.name == name),
+        None => outputs
+            .iter()
+            .filter(|x| x.active)
+            .find(|x| ["eDP", "LVDS", "DSI"].iter().any(|p| x.name.starts_with(p)))
+            .or_else(|| outputs.iter().find(|x| x.active)),
+    };
+
+    let Some(output) = output else {
+        bail!("Output not found: {:?}", name);
+    };
+    let Some(mode) = &output.current_mode else {
+        bail!("Output disabled: {}", output.name);
+    };
+
+    Ok(SwaypedTouchOutput {
+        width: f64::from(mode.width),
+        height: f64::from(mode.height),
+        transform: output.transform.clone().unwrap_or_default(),
+        scale: output.scale.unwrap_or(1.0),
+    })
+}
+
+/// Position on the rotated output of a point of the panel, following the
+/// wlroots output transforms
+fn transform_point((x, y): Point, (width, height): Point, transform: &str) -> Point {
+    match transform {
+        "90" => (height - y, x),
+        "180" => (width - x, height - y),
+        "270" => (y, width - x),
+        "flipped" => (width - x, y),
+        "flipped-90" => (y, x),
+        "flipped-180" => (x, height - y),
+        "flipped-270" => (height - y, width - x),
+        _ => (x, y),
+    }
+}
+
+fn transform_size((width, height): Point, transform: &str) -> Point {
+    match transform {
+        "90" | "270" | "flipped-90" | "flipped-270" => (height, width),
+        _ => (width, height),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::commands::CommandDesc;
+    use crate::config::Mapping;
+
+    #[tokio::test]
+    async fn test_swayped_touch_swipe() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig::default();
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut touch = SwaypedTouch::new(&cmd_desc, &config);
+
+        touch.down(0, (100.0, 50.0), 0);
+        touch.down(1, (120.0, 50.0), 10_000);
+        touch.down(2, (140.0, 52.0), 20_000);
+        for slot in 0..3 {
+            let x = 100.0 + 20.0 * slot as f64;
+            touch.motion(slot, (x - 40.0, 51.0), 200_000);
+        }
+        touch.up(0, 210_000).await.unwrap();
+        touch.up(1, 210_000).await.unwrap();
+        assert!(rx.try_recv().is_err());
+        touch.up(2, 220_000).await.unwrap();
+
+        let (cmd, ctx) = rx.try_recv().unwrap();
+        assert_eq!(cmd, InputCommand::SwipeLeft(3));
+        assert_eq!(ctx.source, Some(Source::Touchscreen));
+        assert_eq!(ctx.duration_ms, 220);
+        assert!(touch.slots.is_empty());
+
+        // single finger scrolling is not a gesture
+        touch.down(0, (100.0, 50.0), 0);
+        touch.motion(0, (100.0, 150.0), 100_000);
+        touch.up(0, 100_000).await.unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_swayped_touch_active() {
+        let (tx, _) = mpsc::channel(1);
+        let config = TomlConfig {
+            mappings: vec![Mapping {
+                gesture: "swipe_left".to_string(),
+                finger_count: Some(3),
+                cmd: "workspace prev".to_string(),
+                cmd_type: "sway".to_string(),
+                source: Some(Source::Touchpad),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let touch = SwaypedTouch::new(&cmd_desc, &config);
+        assert!(!touch.active);
+
+        // sway is not even asked for the output
+        let mut output = watch_output(&cmd_desc, &config);
+        assert!(output.has_changed().is_err());
+        assert!(output.borrow_and_update().is_none());
+
+        let config = TomlConfig {
+            mappings: vec![Mapping {
+                gesture: "edge_left".to_string(),
+                cmd: "exec fuzzel".to_string(),
+                cmd_type: "sway".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let (tx, _) = mpsc::channel(1);
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+        assert!(SwaypedTouch::new(&cmd_desc, &config).active);
+    }
+
+    #[tokio::test]
+    async fn test_swayped_touch_pinch() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig::default();
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut touch = SwaypedTouch::new(&cmd_desc, &config);
+
+        touch.down(0, (50.0, 50.0), 0);
+        touch.down(1, (150.0, 50.0), 0);
+        touch.motion(0, (80.0, 50.0), 100_000);
+        touch.motion(1, (120.0, 50.0), 100_000);
+        touch.up(0, 100_000).await.unwrap();
+        touch.up(1, 100_000).await.unwrap();
+
+        let (cmd, ctx) = rx.try_recv().unwrap();
+        assert_eq!(cmd, InputCommand::PinchIn(2));
+        assert!((ctx.scale - 0.4).abs() < 1e-9);
+
+        // a quarter turn clockwise around the center
+        touch.down(0, (50.0, 50.0), 0);
+        touch.down(1, (150.0, 50.0), 0);
+        touch.motion(0, (100.0, 0.0), 100_000);
+        touch.motion(1, (100.0, 100.0), 100_000);
+        touch.up(0, 100_000).await.unwrap();
+        touch.up(1, 100_000).await.unwrap();
+
+        let (cmd, ctx) = rx.try_recv().unwrap();
+        assert_eq!(cmd, InputCommand::RotateCw(2));
+        assert!((ctx.angle - 90.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_swayped_touch_hold() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig::default();
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut touch = SwaypedTouch::new(&cmd_desc, &config);
+
+        // a two finger tap
+        touch.down(0, (50.0, 50.0), 0);
+        touch.down(1, (70.0, 50.0), 0);
+        touch.up(0, 80_000).await.unwrap();
+        touch.up(1, 80_000).await.unwrap();
+        assert!(rx.try_recv().is_err());
+
+        touch.down(0, (50.0, 50.0), 0);
+        touch.down(1, (70.0, 50.0), 0);
+        touch.motion(1, (71.0, 51.0), 400_000);
+        touch.up(0, 800_000).await.unwrap();
+        touch.up(1, 800_000).await.unwrap();
+
+        let (cmd, ctx) = rx.try_recv().unwrap();
+        assert_eq!(cmd, InputCommand::Hold(2));
+        assert_eq!(ctx.duration_ms, 800);
+
+        // cancelled touches never fire
+        touch.down(0, (50.0, 50.0), 0);
+        touch.down(1, (70.0, 50.0), 0);
+        touch.cancel(0, 800_000).await.unwrap();
+        touch.up(1, 800_000).await.unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_swayped_touch_edge() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let config = TomlConfig::default();
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut touch = SwaypedTouch::new(&cmd_desc, &config);
+        touch.screen = Some((300.0, 200.0));
+
+        touch.down(0, (1.0, 100.0), 0);
+        touch.motion(0, (60.0, 105.0), 100_000);
+        touch.up(0, 100_000).await.unwrap();
+        let (cmd, ctx) = rx.try_recv().unwrap();
+        assert_eq!(cmd, InputCommand::EdgeLeft);
+        assert_eq!(ctx.source, Some(Source::Touchscreen));
+
+        touch.down(0, (150.0, 198.0), 0);
+        touch.motion(0, (150.0, 120.0), 100_000);
+        touch.up(0, 100_000).await.unwrap();
+        assert_eq!(rx.try_recv().unwrap().0, InputCommand::EdgeBottom);
+
+        // along the edge rather than inward
+        touch.down(0, (299.0, 20.0), 0);
+        touch.motion(0, (299.0, 120.0), 100_000);
+        touch.up(0, 100_000).await.unwrap();
+        assert!(rx.try_recv().is_err());
+
+        // away from the edges
+        touch.down(0, (100.0, 100.0), 0);
+        touch.motion(0, (160.0, 100.0), 100_000);
+        touch.up(0, 100_000).await.unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_swayped_touch_output() {
+        let (tx, _) = mpsc::channel(1);
+        let config = TomlConfig::default();
+
+        let cmd_desc = CommandDesc::new(false, &config, tx);
+
+        let mut touch = SwaypedTouch::new(&cmd_desc, &config);
+        touch.size = Some((300.0, 200.0));
+
+        let output = SwaypedTouchOutput {
+            width: 1920.0,
+            height: 1280.0,
+            transform: "normal".to_string(),
+            scale: 1.0,
+        };
+        touch.set_output(Some(output.clone()));
+        assert_eq!(touch.screen, Some((300.0, 200.0)));
+
+        // rotated into portrait
+        touch.set_output(Some(SwaypedTouchOutput {
+            transform: "90".to_string(),
+            ..output
+        }));
+        assert_eq!(touch.screen, Some((200.0, 300.0)));
+        assert_eq!(touch.edge((199.0, 150.0)), Some(InputCommand::EdgeRight));
+
+        touch.set_output(None);
+        assert_eq!(touch.screen, Some((300.0, 200.0)));
+    }
+
+    #[test]
+    fn test_swayped_touch_transform() {
+        let size = (1920.0, 1080.0);
+
+        assert_eq!(transform_point((10.0, 20.0), size, "normal"), (10.0, 20.0));
+        assert_eq!(transform_point((10.0, 20.0), size, "90"), (1060.0, 10.0));
+        assert_eq!(transform_point((10.0, 20.0), size, "180"), (1910.0, 1060.0));
+        assert_eq!(transform_point((10.0, 20.0), size, "270"), (20.0, 1910.0));
+        assert_eq!(transform_size(size, "90"), (1080.0, 1920.0));
+        assert_eq!(transform_size(size, "flipped-180"), size);
+    }
+}